@@ -6,6 +6,12 @@ layout(location=0) in vec2 v_tex_coord;
 layout(set=0, binding=0) uniform texture2D t_tex;
 layout(set=0, binding=1) uniform sampler s_tex;
 
+// per-draw data, bound with a dynamic offset into one shared buffer
+layout(set=2, binding=0)
+uniform Object {
+  vec4 tint;
+};
+
 void main(){
-  color = texture(sampler2D(t_tex, s_tex), v_tex_coord);
+  color = texture(sampler2D(t_tex, s_tex), v_tex_coord) * tint;
 }
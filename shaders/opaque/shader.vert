@@ -3,16 +3,22 @@
 layout(location=0) in vec3 position;
 layout(location=1) in vec2 tex_coord;
 
+// per-instance model matrix, one column per location
+layout(location=2) in vec4 model_0;
+layout(location=3) in vec4 model_1;
+layout(location=4) in vec4 model_2;
+layout(location=5) in vec4 model_3;
+
 layout(location=0) out vec2 f_tex_coord;
 
 layout(set=1, binding=0)
 uniform Uniforms {
-  mat4 model;
   mat4 view;
   mat4 projection;
 };
 
 void main() {
+  mat4 model = mat4(model_0, model_1, model_2, model_3);
   f_tex_coord = tex_coord;
   gl_Position =  projection * view * model * vec4(position, 1.0);
 }
@@ -5,11 +5,17 @@ layout(location=0) in vec2 v_tex_coord;
 layout(set=0, binding=0) uniform texture2D t_tex;
 layout(set=0, binding=1) uniform sampler s_tex;
 
+// per-draw data, bound with a dynamic offset into one shared buffer
+layout(set=2, binding=0)
+uniform Object {
+  vec4 tint;
+};
+
 layout(location=0) out vec4 _accum;
 layout(location=1) out float _revealage;
 
 void main(){
-  vec4 color = texture(sampler2D(t_tex, s_tex), v_tex_coord);
+  vec4 color = texture(sampler2D(t_tex, s_tex), v_tex_coord) * tint;
 
   float weight =
       max(min(1.0, max(max(color.r, color.g), color.b) * color.a), color.a) *
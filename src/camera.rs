@@ -1,4 +1,4 @@
-pub use glam::{Vec3, Mat4};
+pub use glam::{Vec3, Vec4, Mat4, Quat};
 
 // #[cfg_attr(rustfmt, rustfmt_skip)]
 // const OPENGL_TO_WGPU_MATRIX: Mat4 = Mat4::new(
@@ -9,69 +9,184 @@ pub use glam::{Vec3, Mat4};
 // );
 
 
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DepthMode{
+    // the OpenGL style projection, near maps to -1 and `far` to 1
+    Standard,
+    // near maps to 1 and infinity to 0, float depth keeps its precision out to the horizon
+    ReverseInfinite,
+}
+
+impl DepthMode{
+    pub fn compare(self) -> wgpu::CompareFunction{
+        match self{
+            DepthMode::Standard => wgpu::CompareFunction::Less,
+            DepthMode::ReverseInfinite => wgpu::CompareFunction::Greater,
+        }
+    }
+
+    // the value nothing has been drawn at
+    pub fn clear_depth(self) -> f32{
+        match self{
+            DepthMode::Standard => 1.,
+            DepthMode::ReverseInfinite => 0.,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Projection{
+    Perspective,
+    // parallel rays, `Camera::height` world units fill the view vertically
+    Orthographic,
+}
+
+// what the uniforms need, moving it around is up to a CameraController
 pub struct Camera{
     pub eye: Vec3,
+    pub prev_eye: Vec3,
+    pub prev_orientation: Quat,
+    pub prev_fovy: f32,
     pub target: Vec3,
     pub up: Vec3,
     pub aspect: f32,
+    pub projection: Projection,
+    // radians, for Projection::Perspective
     pub fovy: f32,
+    // world units, for Projection::Orthographic
+    pub height: f32,
     pub near: f32,
+    // ignored by DepthMode::ReverseInfinite
     pub far: f32,
-    pub velocity: Vec3,
+    // the renderer builds its depth test for this, changing it later needs a new renderer
+    pub depth: DepthMode,
+    // degrees, kept in sync with `orientation`
     pub yaw: f32,
     pub pitch: f32,
+    pub roll: f32,
+    // rotates the camera's local frame, x forward, y up and z right, into the world
+    pub orientation: Quat,
 }
 
-
-const DAMP: f32 = 0.75;
-const DAMP_LIMIT: f32 = 0.01;
-const SENSITIVITY: f32 = 0.05;
-
 impl Camera{
     pub fn get_view(&self) -> Mat4{
         Mat4::look_at_rh(self.eye, self.eye + self.target, self.up)
     }
 
-    pub fn get_projection(&self) -> Mat4{
-        Mat4::perspective_rh_gl(self.fovy, self.aspect, self.near, self.far)
+    // blends between the previous and the current tick, `alpha` comes from `Timer::get_alpha`
+    pub fn get_interpolated_view(&self, alpha: f32) -> Mat4{
+        let eye = self.prev_eye.lerp(self.eye, alpha);
+        // q and -q are the same rotation, flipping one keeps slerp on the short arc
+        let prev = if self.prev_orientation.dot(self.orientation) < 0. {
+            Quat::from(-Vec4::from(self.prev_orientation))
+        }else{
+            self.prev_orientation
+        };
+        let orientation = prev.slerp(self.orientation, alpha).normalize();
+        let target = orientation * Vec3::unit_x();
+        let up = orientation * Vec3::unit_y();
+        Mat4::look_at_rh(eye, eye + target, up)
     }
 
-    pub fn mouse_update(&mut self, dx: f32, dy: f32){
-        self.yaw += dx * SENSITIVITY;
-        self.pitch += - dy * SENSITIVITY;
-
-        self.pitch = self.pitch.max(-89.9).min(89.9);
-
-        let dx = self.yaw.to_radians().cos() * self.pitch.to_radians().cos();
-        let dy = self.pitch.to_radians().sin();
-        let dz = self.yaw.to_radians().sin() * self.pitch.to_radians().cos();
+    // call at the start of a tick, before moving the camera
+    pub fn save_previous(&mut self){
+        self.prev_eye = self.eye;
+        self.prev_orientation = self.orientation;
+        self.prev_fovy = self.fovy;
+    }
 
-        self.target = Vec3::new(dx, dy, dz).normalize();
+    // a rotation made between ticks, like mouse look, is applied to the previous orientation as well
+    // so it shows up right away instead of being blended in over the next tick
+    pub fn carry_rotation(&mut self, from: Quat){
+        self.prev_orientation = (self.orientation * from.conjugate() * self.prev_orientation).normalize();
     }
 
-    pub fn update(&mut self, dt: f32){
-        self.eye.set_x(self.eye.x() + self.velocity.x() * dt);
-        self.eye.set_y(self.eye.y() + self.velocity.y() * dt);
-        self.eye.set_z(self.eye.z() + self.velocity.z() * dt);
+    pub fn get_projection(&self) -> Mat4{
+        self.projection_with(self.fovy)
+    }
 
-        let mut vx = self.velocity.x() * DAMP;
-        let mut vy = self.velocity.y() * DAMP;
-        let mut vz = self.velocity.z() * DAMP;
+    pub fn get_interpolated_projection(&self, alpha: f32) -> Mat4{
+        self.projection_with(self.prev_fovy + (self.fovy - self.prev_fovy) * alpha)
+    }
 
-        if (vx.is_sign_positive() && vx <= DAMP_LIMIT) || (vx.is_sign_negative() && vx >= -DAMP_LIMIT) {
-            vx = 0.;
+    fn projection_with(&self, fovy: f32) -> Mat4{
+        if self.projection == Projection::Orthographic{
+            let top = self.height * 0.5;
+            let right = top * self.aspect;
+            return match self.depth{
+                DepthMode::Standard => Mat4::orthographic_rh_gl(-right, right, -top, top, self.near, self.far),
+                // a parallel projection can't reach infinity, swapping the planes still gives reverse-Z
+                DepthMode::ReverseInfinite => Mat4::orthographic_rh(-right, right, -top, top, self.far, self.near),
+            };
         }
 
-        if (vy.is_sign_positive() && vy <= DAMP_LIMIT) || (vy.is_sign_negative() && vy >= -DAMP_LIMIT) {
-            vy = 0.;
+        match self.depth{
+            DepthMode::Standard => Mat4::perspective_rh_gl(fovy, self.aspect, self.near, self.far),
+            DepthMode::ReverseInfinite => {
+                // clip z is `near` and w is -z, so depth is near / distance
+                let f = 1. / (fovy * 0.5).tan();
+                Mat4::from_cols_array(&[
+                    f / self.aspect, 0., 0., 0.,
+                    0., f, 0., 0.,
+                    0., 0., 0., -1.,
+                    0., 0., self.near, 0.,
+                ])
+            },
         }
+    }
+
+    pub fn toggle_projection(&mut self){
+        self.projection = match self.projection{
+            Projection::Perspective => Projection::Orthographic,
+            Projection::Orthographic => Projection::Perspective,
+        };
+    }
 
-        if (vz.is_sign_positive() && vz <= DAMP_LIMIT) || (vz.is_sign_negative() && vz >= -DAMP_LIMIT) {
-            vz = 0.;
+    // keeps the view from stretching when the target changes size
+    pub fn set_viewport(&mut self, width: u32, height: u32){
+        if height > 0{
+            self.aspect = width as f32 / height as f32;
         }
+    }
+
+    // yaw turns towards +z, pitch towards +y and roll clockwise, all in degrees
+    pub fn from_euler(yaw: f32, pitch: f32, roll: f32) -> Quat{
+        Quat::from_rotation_y(-yaw.to_radians())
+            * Quat::from_rotation_z(pitch.to_radians())
+            * Quat::from_rotation_x(roll.to_radians())
+    }
+
+    pub fn look(&mut self, yaw: f32, pitch: f32, roll: f32){
+        self.yaw = yaw;
+        self.pitch = pitch;
+        self.roll = roll;
+        self.orientation = Self::from_euler(yaw, pitch, roll);
+        self.update_axes();
+    }
+
+    // the inverse of from_euler, yaw and pitch come from where x points and roll from how far y
+    // is turned away from the unrolled up
+    pub fn to_euler(orientation: Quat) -> (f32, f32, f32){
+        let target = orientation * Vec3::unit_x();
+        let yaw = target.z().atan2(target.x()).to_degrees();
+        let pitch = target.y().max(-1.).min(1.).asin().to_degrees();
+        let up = Self::from_euler(yaw, pitch, 0.).conjugate() * (orientation * Vec3::unit_y());
+        let roll = up.z().atan2(up.y()).to_degrees();
+        (yaw, pitch, roll)
+    }
+
+    // for orientations that didn't come from angles
+    pub fn set_orientation(&mut self, orientation: Quat){
+        let (yaw, pitch, roll) = Self::to_euler(orientation);
+        self.yaw = yaw;
+        self.pitch = pitch;
+        self.roll = roll;
+        self.orientation = orientation;
+        self.update_axes();
+    }
 
-        self.velocity.set_x(vx);
-        self.velocity.set_y(vy);
-        self.velocity.set_z(vz);
+    fn update_axes(&mut self){
+        self.target = (self.orientation * Vec3::unit_x()).normalize();
+        self.up = (self.orientation * Vec3::unit_y()).normalize();
     }
 }
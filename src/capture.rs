@@ -0,0 +1,235 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+// wgpu wants every row of a texture to buffer copy to start at a multiple of this
+pub const COPY_BYTES_PER_ROW_ALIGNMENT: u32 = 256;
+
+pub fn padded_bytes_per_row(width: u32) -> u32{
+    let unpadded = width * 4;
+    let align = COPY_BYTES_PER_ROW_ALIGNMENT;
+    (unpadded + align - 1) / align * align
+}
+
+// mappable buffer for one padded 4 bytes per pixel frame
+pub fn create_buffer(device: &wgpu::Device, width: u32, height: u32) -> wgpu::Buffer{
+    device.create_buffer(&wgpu::BufferDescriptor{
+        label: Some("capture buffer"),
+        size: (padded_bytes_per_row(width) * height) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsage::COPY_DST | wgpu::BufferUsage::MAP_READ,
+    })
+}
+
+// copies a 4 bytes per pixel texture into a buffer from `create_buffer`, returns the row pitch
+pub fn copy_to_buffer(encoder: &mut wgpu::CommandEncoder, texture: &wgpu::Texture, buffer: &wgpu::Buffer, width: u32, height: u32) -> u32{
+    let bytes_per_row = padded_bytes_per_row(width);
+    encoder.copy_texture_to_buffer(
+        wgpu::TextureCopyView{
+            texture,
+            mip_level: 0,
+            array_layer: 0,
+            origin: wgpu::Origin3d::ZERO,
+        },
+        wgpu::BufferCopyView{
+            buffer,
+            offset: 0,
+            bytes_per_row,
+            rows_per_image: height,
+        },
+        wgpu::Extent3d{
+            width,
+            height,
+            depth: 1,
+        },
+    );
+
+    bytes_per_row
+}
+
+// strips the row padding and swizzles BGRA targets into RGBA, alpha is forced opaque like the presented frame
+pub fn to_rgba(data: &[u8], format: wgpu::TextureFormat, width: u32, height: u32, bytes_per_row: u32) -> Result<image::RgbaImage, anyhow::Error>{
+    let bgra = match format{
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+        _ => anyhow::bail!("Can't capture texture format {:?}", format),
+    };
+
+    let row_len = (width * 4) as usize;
+    let mut pixels = Vec::with_capacity(row_len * height as usize);
+    for row in data.chunks(bytes_per_row as usize).take(height as usize){
+        for pixel in row[..row_len].chunks(4){
+            if bgra{
+                pixels.extend_from_slice(&[pixel[2], pixel[1], pixel[0], 255]);
+            }else{
+                pixels.extend_from_slice(&[pixel[0], pixel[1], pixel[2], 255]);
+            }
+        }
+    }
+
+    image::RgbaImage::from_raw(width, height, pixels).ok_or_else(|| anyhow::anyhow!("Capture buffer is too small"))
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SequenceFormat{
+    // numbered PNG files inside a directory
+    Png,
+    // one uncompressed 4:4:4 YUV4MPEG2 stream
+    Y4m,
+}
+
+enum Sink{
+    Png(PathBuf),
+    Y4m{
+        file: BufWriter<File>,
+        size: Option<(u32, u32)>,
+    },
+}
+
+// writes `frames` captured frames, the simulation is expected to advance by `frame_step` between them
+pub struct SequenceRecorder{
+    sink: Sink,
+    fps: u32,
+    frames: u32,
+    written: u32,
+}
+
+impl SequenceRecorder{
+    // `max_step` is the longest frame the timer simulates in full, see Timer::get_max_frame
+    pub fn new(path: &Path, format: SequenceFormat, fps: u32, frames: u32, max_step: Duration) -> Result<Self, anyhow::Error>{
+        if fps == 0{
+            anyhow::bail!("Sequence needs at least one frame per second");
+        }
+        if frames == 0{
+            anyhow::bail!("Sequence needs at least one frame");
+        }
+        let step = Duration::from_secs(1) / fps;
+        if step > max_step{
+            anyhow::bail!("A frame at {} FPS is {:?}, the timer only simulates {:?} per frame", fps, step, max_step);
+        }
+
+        let sink = match format{
+            SequenceFormat::Png => {
+                std::fs::create_dir_all(path)?;
+                Sink::Png(path.to_path_buf())
+            },
+            SequenceFormat::Y4m => {
+                if let Some(parent) = path.parent(){
+                    std::fs::create_dir_all(parent)?;
+                }
+                Sink::Y4m{
+                    file: BufWriter::new(File::create(path)?),
+                    size: None,
+                }
+            },
+        };
+
+        Ok(Self{
+            sink,
+            fps,
+            frames,
+            written: 0,
+        })
+    }
+
+    pub fn frame_step(&self) -> Duration{
+        Duration::from_secs(1) / self.fps
+    }
+
+    pub fn is_finished(&self) -> bool{
+        self.written >= self.frames
+    }
+
+    pub fn get_written(&self) -> u32{
+        self.written
+    }
+
+    pub fn write_frame(&mut self, img: &image::RgbaImage) -> Result<(), anyhow::Error>{
+        match &mut self.sink{
+            Sink::Png(dir) => {
+                img.save(dir.join(format!("{:05}.png", self.written)))?;
+            },
+            Sink::Y4m{ file, size } => {
+                let dim = img.dimensions();
+                if size.is_none(){
+                    writeln!(file, "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444", dim.0, dim.1, self.fps)?;
+                    *size = Some(dim);
+                }
+                if *size != Some(dim){
+                    anyhow::bail!("Frame size changed from {:?} to {:?} while recording", size, dim);
+                }
+
+                writeln!(file, "FRAME")?;
+                file.write_all(&to_yuv444(img))?;
+            },
+        }
+
+        self.written += 1;
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<(), anyhow::Error>{
+        if let Sink::Y4m{ mut file, .. } = self.sink{
+            file.flush()?;
+        }
+        Ok(())
+    }
+}
+
+// planar Y, Cb, Cr in BT.601 studio range, which is what Y4M players assume
+fn to_yuv444(img: &image::RgbaImage) -> Vec<u8>{
+    let len = (img.width() * img.height()) as usize;
+    let mut planes = vec![0u8; len * 3];
+    for (i, pixel) in img.pixels().enumerate(){
+        let r = pixel[0] as f32;
+        let g = pixel[1] as f32;
+        let b = pixel[2] as f32;
+
+        let y = 16. + 0.257 * r + 0.504 * g + 0.098 * b;
+        let u = 128. - 0.148 * r - 0.291 * g + 0.439 * b;
+        let v = 128. + 0.439 * r - 0.368 * g - 0.071 * b;
+
+        planes[i] = y.round() as u8;
+        planes[len + i] = u.round() as u8;
+        planes[len * 2 + i] = v.round() as u8;
+    }
+    planes
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn pads_rows_to_the_copy_alignment(){
+        assert_eq!(padded_bytes_per_row(64), 256);
+        assert_eq!(padded_bytes_per_row(65), 512);
+    }
+
+    #[test]
+    fn strips_padding_and_swizzles_bgra(){
+        let (width, height) = (65, 2);
+        let bytes_per_row = padded_bytes_per_row(width);
+        // every pixel is B=1 G=2 R=3 A=0, the padding is 255 so any of it leaking through shows up
+        let mut data = vec![255u8; (bytes_per_row * height) as usize];
+        for row in data.chunks_mut(bytes_per_row as usize){
+            for pixel in row[..(width * 4) as usize].chunks_mut(4){
+                pixel.copy_from_slice(&[1, 2, 3, 0]);
+            }
+        }
+
+        let img = to_rgba(&data, wgpu::TextureFormat::Bgra8UnormSrgb, width, height, bytes_per_row).unwrap();
+        assert_eq!(img.dimensions(), (width, height));
+        assert!(img.pixels().all(|p| p.0 == [3, 2, 1, 255]));
+    }
+
+    #[test]
+    fn keeps_rgba_channels(){
+        let bytes_per_row = padded_bytes_per_row(1);
+        let mut data = vec![0u8; bytes_per_row as usize];
+        data[..4].copy_from_slice(&[1, 2, 3, 0]);
+
+        let img = to_rgba(&data, wgpu::TextureFormat::Rgba8Unorm, 1, 1, bytes_per_row).unwrap();
+        assert_eq!(img.get_pixel(0, 0).0, [1, 2, 3, 255]);
+    }
+}
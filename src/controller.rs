@@ -0,0 +1,327 @@
+use glam::{Vec3, Quat};
+use crate::camera::{Camera, Projection};
+use crate::input::{InputState, Action};
+
+const DAMP: f32 = 0.75;
+const DAMP_LIMIT: f32 = 0.01;
+const SENSITIVITY: f32 = 0.05;
+const SPEED: f32 = 20.;
+// degrees per second
+const ROLL_SPEED: f32 = 90.;
+// degrees of fovy per wheel step
+const ZOOM_STEP: f32 = 2.;
+// scales the orthographic view height per wheel step
+const ZOOM_FACTOR: f32 = 1.1;
+pub const PITCH_LIMIT: f32 = 89.9;
+
+const WALK_SPEED: f32 = 4.;
+const JUMP_SPEED: f32 = 5.;
+// letting go of jump while still rising keeps this much of the upward speed, for short hops
+const JUMP_CUT: f32 = 0.5;
+const GRAVITY: f32 = 9.81;
+
+const TURNTABLE_DISTANCE: f32 = 4.;
+// degrees per second
+const TURNTABLE_SPEED: f32 = 20.;
+
+// -1, 0 or 1 depending on which of the two actions is held
+pub fn axis(input: &InputState, negative: Action, positive: Action) -> f32{
+    let mut value = 0.;
+    if input.is_action_held(negative){
+        value -= 1.;
+    }
+    if input.is_action_held(positive){
+        value += 1.;
+    }
+    value
+}
+
+fn damp(value: f32) -> f32{
+    let value = value * DAMP;
+    if value.abs() <= DAMP_LIMIT{
+        return 0.;
+    }
+    value
+}
+
+// moves a Camera, the renderer keeps one of each and only the active one gets input
+pub trait CameraController{
+    fn name(&self) -> &str;
+
+    // takes over the camera's pose, used at startup and when a scene is loaded
+    fn reset(&mut self, camera: &Camera);
+
+    // puts the camera where this controller left it
+    fn activate(&mut self, camera: &mut Camera);
+
+    fn mouse_motion(&mut self, camera: &mut Camera, dx: f32, dy: f32, input: &InputState);
+
+    // positive steps zoom in
+    fn zoom(&mut self, camera: &mut Camera, steps: f32){
+        match camera.projection{
+            Projection::Perspective => {
+                camera.fovy = (camera.fovy - (steps * ZOOM_STEP).to_radians()).max(45f32.to_radians()).min(120f32.to_radians());
+            },
+            Projection::Orthographic => {
+                camera.height = (camera.height * ZOOM_FACTOR.powf(-steps)).max(0.1);
+            },
+        }
+    }
+
+    // once per fixed tick
+    fn update(&mut self, camera: &mut Camera, dt: f32, input: &InputState);
+}
+
+// free flying with velocity along the world axes
+pub struct Fly{
+    eye: Vec3,
+    velocity: Vec3,
+    yaw: f32,
+    pitch: f32,
+    roll: f32,
+    orientation: Quat,
+    // degrees either side of the horizon, `None` rotates around the camera's own axes like a flight sim
+    pub pitch_limit: Option<f32>,
+}
+
+impl Fly{
+    pub fn new(camera: &Camera) -> Self{
+        let mut fly = Self{
+            eye: camera.eye,
+            velocity: Vec3::zero(),
+            yaw: 0.,
+            pitch: 0.,
+            roll: 0.,
+            orientation: Quat::identity(),
+            pitch_limit: Some(PITCH_LIMIT),
+        };
+        fly.reset(camera);
+        fly
+    }
+
+    // with a pitch limit yaw stays around the world up so the horizon can't tilt from turning
+    pub fn rotate(&mut self, yaw: f32, pitch: f32, roll: f32){
+        match self.pitch_limit{
+            Some(limit) => {
+                self.yaw += yaw;
+                self.pitch = (self.pitch + pitch).max(-limit).min(limit);
+                self.roll += roll;
+                self.orientation = Camera::from_euler(self.yaw, self.pitch, self.roll);
+            },
+            None => {
+                // turning around the local axes also rolls, so all three angles are read back
+                self.orientation = (self.orientation * Camera::from_euler(yaw, pitch, roll)).normalize();
+                let (yaw, pitch, roll) = Camera::to_euler(self.orientation);
+                self.yaw = yaw;
+                self.pitch = pitch;
+                self.roll = roll;
+            },
+        }
+    }
+
+    // switching back to a limited pitch keeps the heading and roll and levels the pitch into the limit
+    pub fn toggle_pitch_limit(&mut self){
+        self.pitch_limit = match self.pitch_limit{
+            Some(_) => None,
+            None => Some(PITCH_LIMIT),
+        };
+        self.rotate(0., 0., 0.);
+    }
+
+    fn apply(&self, camera: &mut Camera){
+        camera.eye = self.eye;
+        camera.set_orientation(self.orientation);
+    }
+}
+
+impl CameraController for Fly{
+    fn name(&self) -> &str{
+        "fly"
+    }
+
+    fn reset(&mut self, camera: &Camera){
+        self.eye = camera.eye;
+        self.velocity = Vec3::zero();
+        self.yaw = camera.yaw;
+        self.pitch = camera.pitch;
+        self.roll = camera.roll;
+        self.orientation = camera.orientation;
+    }
+
+    fn activate(&mut self, camera: &mut Camera){
+        self.apply(camera);
+        camera.save_previous();
+    }
+
+    fn mouse_motion(&mut self, camera: &mut Camera, dx: f32, dy: f32, _input: &InputState){
+        let from = camera.orientation;
+        self.rotate(dx * SENSITIVITY, - dy * SENSITIVITY, 0.);
+        self.apply(camera);
+        camera.carry_rotation(from);
+    }
+
+    fn update(&mut self, camera: &mut Camera, dt: f32, input: &InputState){
+        camera.save_previous();
+
+        if input.was_action_pressed(Action::FreeLook){
+            self.toggle_pitch_limit();
+        }
+
+        let roll = axis(input, Action::RollLeft, Action::RollRight);
+        if roll != 0. { self.rotate(0., 0., roll * ROLL_SPEED * dt); }
+
+        let x = axis(input, Action::MoveLeft, Action::MoveRight);
+        let y = axis(input, Action::Crouch, Action::Jump);
+        let z = axis(input, Action::MoveForward, Action::MoveBackward);
+        if x != 0. { self.velocity.set_x(x * SPEED); }
+        if y != 0. { self.velocity.set_y(y * SPEED); }
+        if z != 0. { self.velocity.set_z(z * SPEED); }
+
+        self.eye += self.velocity * dt;
+
+        self.velocity.set_x(damp(self.velocity.x()));
+        self.velocity.set_y(damp(self.velocity.y()));
+        self.velocity.set_z(damp(self.velocity.z()));
+
+        self.apply(camera);
+    }
+}
+
+// stays at the height it was reset at, moves where it faces and can jump
+pub struct Walk{
+    eye: Vec3,
+    yaw: f32,
+    pitch: f32,
+    ground: f32,
+    fall: f32,
+}
+
+impl Walk{
+    pub fn new(camera: &Camera) -> Self{
+        let mut walk = Self{
+            eye: camera.eye,
+            yaw: 0.,
+            pitch: 0.,
+            ground: 0.,
+            fall: 0.,
+        };
+        walk.reset(camera);
+        walk
+    }
+
+    fn apply(&self, camera: &mut Camera){
+        camera.eye = self.eye;
+        camera.look(self.yaw, self.pitch, 0.);
+    }
+}
+
+impl CameraController for Walk{
+    fn name(&self) -> &str{
+        "walk"
+    }
+
+    fn reset(&mut self, camera: &Camera){
+        self.eye = camera.eye;
+        self.yaw = camera.yaw;
+        self.pitch = camera.pitch.max(-PITCH_LIMIT).min(PITCH_LIMIT);
+        self.ground = camera.eye.y();
+        self.fall = 0.;
+    }
+
+    fn activate(&mut self, camera: &mut Camera){
+        self.apply(camera);
+        camera.save_previous();
+    }
+
+    fn mouse_motion(&mut self, camera: &mut Camera, dx: f32, dy: f32, _input: &InputState){
+        let from = camera.orientation;
+        self.yaw += dx * SENSITIVITY;
+        self.pitch = (self.pitch - dy * SENSITIVITY).max(-PITCH_LIMIT).min(PITCH_LIMIT);
+        self.apply(camera);
+        camera.carry_rotation(from);
+    }
+
+    fn update(&mut self, camera: &mut Camera, dt: f32, input: &InputState){
+        camera.save_previous();
+
+        let (sin, cos) = self.yaw.to_radians().sin_cos();
+        let forward = Vec3::new(cos, 0., sin);
+        let right = Vec3::new(-sin, 0., cos);
+
+        let x = axis(input, Action::MoveLeft, Action::MoveRight);
+        let z = axis(input, Action::MoveBackward, Action::MoveForward);
+        let step = forward * z + right * x;
+        if step.length() > 0.{
+            self.eye += step.normalize() * WALK_SPEED * dt;
+        }
+
+        let grounded = self.eye.y() <= self.ground;
+        if grounded && input.was_action_pressed(Action::Jump){
+            self.fall = JUMP_SPEED;
+        }else if self.fall > 0. && input.was_action_released(Action::Jump){
+            self.fall *= JUMP_CUT;
+        }
+        self.fall -= GRAVITY * dt;
+        self.eye.set_y(self.eye.y() + self.fall * dt);
+        if self.eye.y() <= self.ground{
+            self.eye.set_y(self.ground);
+            self.fall = 0.;
+        }
+
+        self.apply(camera);
+    }
+}
+
+// scripted camera that circles a point at a fixed speed, for showing off a scene hands free
+pub struct Turntable{
+    focus: Vec3,
+    // degrees, same convention as Camera::yaw
+    angle: f32,
+    pitch: f32,
+    pub distance: f32,
+    pub speed: f32,
+}
+
+impl Turntable{
+    pub fn new(camera: &Camera) -> Self{
+        let mut turntable = Self{
+            focus: Vec3::zero(),
+            angle: 0.,
+            pitch: 0.,
+            distance: TURNTABLE_DISTANCE,
+            speed: TURNTABLE_SPEED,
+        };
+        turntable.reset(camera);
+        turntable
+    }
+
+    fn apply(&self, camera: &mut Camera){
+        camera.look(self.angle, self.pitch, 0.);
+        camera.eye = self.focus - camera.target * self.distance;
+    }
+}
+
+impl CameraController for Turntable{
+    fn name(&self) -> &str{
+        "turntable"
+    }
+
+    fn reset(&mut self, camera: &Camera){
+        self.focus = camera.eye + camera.target * self.distance;
+        self.angle = camera.yaw;
+        self.pitch = camera.pitch;
+    }
+
+    fn activate(&mut self, camera: &mut Camera){
+        self.apply(camera);
+        camera.save_previous();
+    }
+
+    fn mouse_motion(&mut self, _camera: &mut Camera, _dx: f32, _dy: f32, _input: &InputState){}
+
+    fn update(&mut self, camera: &mut Camera, dt: f32, _input: &InputState){
+        camera.save_previous();
+        self.angle += self.speed * dt;
+        self.apply(camera);
+    }
+}
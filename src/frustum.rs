@@ -0,0 +1,160 @@
+use glam::{Vec3, Vec4, Mat4};
+use crate::camera::DepthMode;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Aabb{
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb{
+    pub fn new(min: Vec3, max: Vec3) -> Self{
+        Self{
+            min,
+            max,
+        }
+    }
+
+    // `None` for no points
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Option<Self>{
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        Some(points.fold(Self::new(first, first), |aabb, p| Self::new(aabb.min.min(p), aabb.max.max(p))))
+    }
+
+    pub fn center(&self) -> Vec3{
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn extents(&self) -> Vec3{
+        (self.max - self.min) * 0.5
+    }
+
+    // the box around the transformed box, looser than the exact corners but only needs one pass over the matrix
+    pub fn transform(&self, matrix: &Mat4) -> Self{
+        let center = matrix.transform_point3(self.center());
+        let e = self.extents();
+        let abs = |v: Vec4| Vec3::new(v.x().abs(), v.y().abs(), v.z().abs());
+        let extents = abs(matrix.x_axis()) * e.x() + abs(matrix.y_axis()) * e.y() + abs(matrix.z_axis()) * e.z();
+        Self::new(center - extents, center + extents)
+    }
+}
+
+// planes point inwards, xyz is the normal and w the distance, a point p is inside when dot(xyz, p) + w >= 0
+#[derive(Copy, Clone, Debug)]
+pub struct Frustum{
+    planes: [Vec4; 6],
+}
+
+impl Frustum{
+    // Gribb/Hartmann extraction, `depth` tells which clip space depth range the matrix uses
+    pub fn from_matrix(view_projection: &Mat4, depth: DepthMode) -> Self{
+        let m = view_projection.transpose();
+        let (x, y, z, w) = (m.x_axis(), m.y_axis(), m.z_axis(), m.w_axis());
+
+        // the lower depth bound, for reverse-Z that is the far plane at infinity which has no normal
+        // and every point is in front of
+        let min_depth = match depth{
+            DepthMode::Standard => w + z,
+            DepthMode::ReverseInfinite => z,
+        };
+        let mut planes = [w + x, w - x, w + y, w - y, min_depth, w - z];
+        for plane in &mut planes{
+            let len = plane.truncate().length();
+            if len > 0.{
+                *plane = *plane / len;
+            }
+        }
+
+        Self{
+            planes,
+        }
+    }
+
+    fn distance(plane: Vec4, point: Vec3) -> f32{
+        plane.truncate().dot(point) + plane.w()
+    }
+
+    // conservative, boxes near the frustum corners can pass while being outside
+    pub fn contains_aabb(&self, aabb: &Aabb) -> bool{
+        let center = aabb.center();
+        let extents = aabb.extents();
+        self.planes.iter().all(|plane| {
+            let n = plane.truncate();
+            let radius = extents.x() * n.x().abs() + extents.y() * n.y().abs() + extents.z() * n.z().abs();
+            Self::distance(*plane, center) >= -radius
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use glam::Quat;
+    use crate::camera::{Camera, Projection};
+
+    // at the origin looking down +z
+    fn camera(depth: DepthMode) -> Camera{
+        let mut camera = Camera{
+            eye: Vec3::zero(),
+            prev_eye: Vec3::zero(),
+            prev_orientation: Quat::identity(),
+            prev_fovy: 90f32.to_radians(),
+            target: Vec3::unit_x(),
+            up: Vec3::unit_y(),
+            aspect: 1.,
+            projection: Projection::Perspective,
+            fovy: 90f32.to_radians(),
+            height: 4.,
+            near: 0.1,
+            far: 100.,
+            depth,
+            yaw: 0.,
+            pitch: 0.,
+            roll: 0.,
+            orientation: Quat::identity(),
+        };
+        camera.look(90., 0., 0.);
+        camera
+    }
+
+    fn frustum(camera: &Camera) -> Frustum{
+        Frustum::from_matrix(&(camera.get_projection() * camera.get_view()), camera.depth)
+    }
+
+    fn cube(z: f32) -> Aabb{
+        Aabb::new(Vec3::new(-1., -1., z - 1.), Vec3::new(1., 1., z + 1.))
+    }
+
+    #[test]
+    fn standard_culls_behind_and_past_far(){
+        let frustum = frustum(&camera(DepthMode::Standard));
+        assert!(frustum.contains_aabb(&cube(10.)));
+        assert!(!frustum.contains_aabb(&cube(-10.)));
+        assert!(!frustum.contains_aabb(&cube(200.)));
+        // off to the side of a 90 degree view
+        assert!(!frustum.contains_aabb(&cube(10.).transform(&Mat4::from_translation(Vec3::new(20., 0., 0.)))));
+    }
+
+    #[test]
+    fn reverse_infinite_culls_behind_only(){
+        let frustum = frustum(&camera(DepthMode::ReverseInfinite));
+        assert!(frustum.contains_aabb(&cube(10.)));
+        assert!(!frustum.contains_aabb(&cube(-10.)));
+        // there is no far plane
+        assert!(frustum.contains_aabb(&cube(1e6)));
+        assert!(!frustum.contains_aabb(&cube(10.).transform(&Mat4::from_translation(Vec3::new(20., 0., 0.)))));
+    }
+
+    #[test]
+    fn orthographic_culls_for_both_depth_modes(){
+        for &depth in &[DepthMode::Standard, DepthMode::ReverseInfinite]{
+            let mut camera = camera(depth);
+            camera.projection = Projection::Orthographic;
+            let frustum = frustum(&camera);
+            assert!(frustum.contains_aabb(&cube(10.)), "{:?}", depth);
+            assert!(!frustum.contains_aabb(&cube(-10.)), "{:?}", depth);
+            assert!(!frustum.contains_aabb(&cube(200.)), "{:?}", depth);
+        }
+    }
+}
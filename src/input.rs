@@ -0,0 +1,201 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use serde::{Serialize, Deserialize};
+use winit::event::{VirtualKeyCode, MouseButton};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action{
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    Jump,
+    Crouch,
+    RollLeft,
+    RollRight,
+    FreeLook,
+    ZoomIn,
+    ZoomOut,
+    Orbit,
+    Pan,
+    SwitchCamera,
+    ToggleProjection,
+    Pause,
+    DebugPause,
+    Step,
+    SlowDown,
+    SpeedUp,
+    ResetSpeed,
+    Screenshot,
+    RecordSequence,
+    SaveScene,
+    AddKeyframe,
+    SavePath,
+    PlayPath,
+    Menu,
+    Quit,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Binding{
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+    WheelUp,
+    WheelDown,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InputMap{
+    bindings: HashMap<Action, Vec<Binding>>,
+}
+
+impl Default for InputMap{
+    fn default() -> Self{
+        use Binding::*;
+        let bindings = [
+            (Action::MoveForward, vec![Key(VirtualKeyCode::W)]),
+            (Action::MoveBackward, vec![Key(VirtualKeyCode::S)]),
+            (Action::MoveLeft, vec![Key(VirtualKeyCode::A)]),
+            (Action::MoveRight, vec![Key(VirtualKeyCode::D)]),
+            (Action::Jump, vec![Key(VirtualKeyCode::Space)]),
+            (Action::Crouch, vec![Key(VirtualKeyCode::LShift)]),
+            (Action::RollLeft, vec![Key(VirtualKeyCode::Z)]),
+            (Action::RollRight, vec![Key(VirtualKeyCode::C)]),
+            (Action::FreeLook, vec![Key(VirtualKeyCode::F)]),
+            (Action::ZoomIn, vec![WheelUp]),
+            (Action::ZoomOut, vec![WheelDown]),
+            (Action::Orbit, vec![Mouse(MouseButton::Left)]),
+            (Action::Pan, vec![Key(VirtualKeyCode::LControl)]),
+            (Action::SwitchCamera, vec![Key(VirtualKeyCode::Tab)]),
+            (Action::ToggleProjection, vec![Key(VirtualKeyCode::Numpad5)]),
+            (Action::Pause, vec![Key(VirtualKeyCode::P)]),
+            (Action::DebugPause, vec![Key(VirtualKeyCode::Comma)]),
+            (Action::Step, vec![Key(VirtualKeyCode::Period)]),
+            (Action::SlowDown, vec![Key(VirtualKeyCode::Minus)]),
+            (Action::SpeedUp, vec![Key(VirtualKeyCode::Equals)]),
+            (Action::ResetSpeed, vec![Key(VirtualKeyCode::Back)]),
+            (Action::Screenshot, vec![Key(VirtualKeyCode::F12)]),
+            (Action::RecordSequence, vec![Key(VirtualKeyCode::F11)]),
+            (Action::SaveScene, vec![Key(VirtualKeyCode::F5)]),
+            (Action::AddKeyframe, vec![Key(VirtualKeyCode::K)]),
+            (Action::SavePath, vec![Key(VirtualKeyCode::F6)]),
+            (Action::PlayPath, vec![Key(VirtualKeyCode::L)]),
+            (Action::Menu, vec![Key(VirtualKeyCode::Escape)]),
+            (Action::Quit, vec![Key(VirtualKeyCode::Q)]),
+        ].iter().cloned().collect();
+
+        Self{
+            bindings,
+        }
+    }
+}
+
+impl InputMap{
+    // the file only lists the actions it rebinds, everything else keeps its default binding.
+    // an action listed with no bindings is left unbound
+    pub fn load(path: &Path) -> Result<Self, anyhow::Error>{
+        let src = std::fs::read_to_string(path)?;
+        let overrides: Self = ron::de::from_str(&src)?;
+
+        let mut map = Self::default();
+        for (action, bindings) in overrides.bindings{
+            map.unbind(action);
+            for binding in bindings{
+                map.bind(action, binding);
+            }
+        }
+        Ok(map)
+    }
+
+    // adds `binding` to `action`, removing it from any other action first
+    pub fn bind(&mut self, action: Action, binding: Binding){
+        for bindings in self.bindings.values_mut(){
+            bindings.retain(|b| *b != binding);
+        }
+        self.bindings.entry(action).or_insert_with(Vec::new).push(binding);
+    }
+
+    pub fn unbind(&mut self, action: Action){
+        self.bindings.remove(&action);
+    }
+
+    pub fn get_bindings(&self, action: Action) -> &[Binding]{
+        self.bindings.get(&action).map(|b| b.as_slice()).unwrap_or(&[])
+    }
+
+    pub fn actions(&self, binding: Binding) -> impl Iterator<Item = Action> + '_{
+        self.bindings.iter()
+            .filter(move |(_, bindings)| bindings.contains(&binding))
+            .map(|(action, _)| *action)
+    }
+}
+
+// binding state across fixed ticks, `pressed` and `released` are edges kept until `end_tick`
+pub struct InputState{
+    pub map: InputMap,
+    down: HashSet<Binding>,
+    pressed: HashSet<Binding>,
+    released: HashSet<Binding>,
+}
+
+impl InputState{
+    pub fn new(map: InputMap) -> Self{
+        Self{
+            map,
+            down: HashSet::new(),
+            pressed: HashSet::new(),
+            released: HashSet::new(),
+        }
+    }
+
+    // returns false for key repeats, which send more presses while the key is down
+    pub fn press(&mut self, binding: Binding) -> bool{
+        if self.down.insert(binding){
+            self.pressed.insert(binding);
+            return true;
+        }
+        false
+    }
+
+    pub fn release(&mut self, binding: Binding){
+        if self.down.remove(&binding){
+            self.released.insert(binding);
+        }
+    }
+
+    // drops everything that is down, e.g. when the window loses focus
+    pub fn release_all(&mut self){
+        for binding in self.down.drain(){
+            self.released.insert(binding);
+        }
+    }
+
+    pub fn end_tick(&mut self){
+        self.pressed.clear();
+        self.released.clear();
+    }
+
+    pub fn is_held(&self, binding: Binding) -> bool{
+        self.down.contains(&binding)
+    }
+
+    pub fn was_pressed(&self, binding: Binding) -> bool{
+        self.pressed.contains(&binding)
+    }
+
+    pub fn was_released(&self, binding: Binding) -> bool{
+        self.released.contains(&binding)
+    }
+
+    pub fn is_action_held(&self, action: Action) -> bool{
+        self.map.get_bindings(action).iter().any(|b| self.is_held(*b))
+    }
+
+    pub fn was_action_pressed(&self, action: Action) -> bool{
+        self.map.get_bindings(action).iter().any(|b| self.was_pressed(*b))
+    }
+
+    pub fn was_action_released(&self, action: Action) -> bool{
+        self.map.get_bindings(action).iter().any(|b| self.was_released(*b))
+    }
+}
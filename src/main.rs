@@ -8,16 +8,35 @@ use winit::{
 mod renderer;
 use renderer::Renderer;
 mod texture;
+mod mesh;
+mod scene;
+mod scene_file;
+mod frustum;
+use scene_file::SceneFile;
+mod capture;
+mod staging;
+use capture::{SequenceFormat, SequenceRecorder};
 
 mod camera;
+use camera::DepthMode;
+mod controller;
+mod orbit;
+mod path;
+use path::{CameraPath, Keyframe};
 mod timer;
+mod stats;
+use stats::FrameStats;
+mod input;
+use input::{InputMap, InputState, Action, Binding};
+mod replay;
+use replay::{InputEvent, Recording, Replay};
+mod state;
+use state::GameState;
 
 /*
 TODO:
 
 - FPS Camera (vectors)
-- FPS camera (quaternions)
-- Arcball camera (quaternions)
 - Chunk
 
 - Logging
@@ -33,6 +52,8 @@ pub struct Game{
     event_loop: EventLoop<()>,
     window: Window,
     renderer: Renderer,
+    input: InputState,
+    state: GameState,
     running: bool,
 }
 
@@ -43,19 +64,24 @@ impl Game{
             .build(&event_loop)
             .unwrap();
 
-        if let Err(e) = window.set_cursor_grab(true){
-            println!("Couldn't grab mouse, error: {}", e);
-        }
-
-        window.set_cursor_visible(false);
+        let mut renderer = block_on(Renderer::new(&window, depth_mode()));
+        load_start_scene(&mut renderer);
 
-        let renderer = block_on(Renderer::new(&window));
+        let input_path = std::path::Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/res/input.ron"));
+        let input_map = InputMap::load(input_path).unwrap_or_else(|e| {
+            println!("Couldn't load input bindings, using defaults, error: {}", e);
+            InputMap::default()
+        });
+        let input = InputState::new(input_map);
 
+        let state = GameState::Playing;
         let running = true;
         Self{
             event_loop,
             window,
             renderer,
+            input,
+            state,
             running,
         }
     }
@@ -65,99 +91,220 @@ impl Game{
             event_loop,
             window,
             mut renderer,
-            mut running,
+            mut input,
+            state,
+            running,
         } = self;
 
-        let mut timer = timer::Timer::new();
-        let mut c_timer = std::time::Instant::now();
-        let mut updates = 0;
-        let mut frames = 0;
+        let mut controls = Controls{
+            timer: timer::Timer::new(),
+            state,
+            sequence: None,
+            path: CameraPath::new(),
+            debug_pause: false,
+            running,
+        };
+        controls.state.enter(&window, &mut controls.timer, &mut renderer);
+
+        let mut c_timer = controls.timer.now();
+        let mut alpha = 0.;
+
+        // set FRAME_STATS to a path to dump every frame as CSV on exit
+        let trace_path = std::env::var_os("FRAME_STATS").map(std::path::PathBuf::from);
+        let mut stats = match trace_path{
+            Some(_) => FrameStats::with_trace(),
+            None => FrameStats::new(),
+        };
+
+        // RECORD_INPUT saves the input of this run, REPLAY_INPUT plays one back instead of live input
+        let record_path = std::env::var_os("RECORD_INPUT").map(std::path::PathBuf::from);
+        let mut recording = record_path.as_ref().map(|_| Recording::new(controls.timer.get_ups()));
+        let mut replay = std::env::var_os("REPLAY_INPUT").and_then(|path| {
+            match Recording::load(std::path::Path::new(&path)){
+                Ok(recording) => Some(Replay::new(recording)),
+                Err(e) => {
+                    println!("Couldn't load input replay, error: {}", e);
+                    None
+                },
+            }
+        });
+        if let Some(replay) = &replay{
+            controls.timer.set_ups(replay.get_ups());
+        }
 
         event_loop.run(move |event, _, control_flow| {
             *control_flow = ControlFlow::Poll;
-            if !running{
+            if !controls.running{
                 *control_flow = ControlFlow::Exit;
             }
 
-            if c_timer.elapsed() >= std::time::Duration::from_secs(1){
-                println!("UPS: {} FPS:{} DT: {}", updates, frames, timer.get_delta().as_secs_f32());
-                updates = 0;
-                frames = 0;
-                c_timer = std::time::Instant::now();
+            if controls.timer.now() - c_timer >= std::time::Duration::from_secs(1){
+                if let (Some(frame), Some(update)) = (stats.frames.summary(), stats.updates.summary()){
+                    let staging = renderer.get_staging();
+                    let (visible, culled) = renderer.get_cull_stats();
+                    println!("FRAME {} | UPDATE {} | DROPPED: {} | UPLOAD: {} bytes/frame in {} chunks | VISIBLE: {} CULLED: {}",
+                        frame, update, controls.timer.get_dropped().as_secs_f32(), staging.get_frame_bytes(), staging.get_chunk_count(), visible, culled);
+                }
+                c_timer = controls.timer.now();
             }
 
             match event {
+                Event::LoopDestroyed => {
+                    if let (Some(path), Some(recording)) = (&record_path, &recording){
+                        match recording.save(path){
+                            Ok(()) => println!("Input recording written to: {:?}", path),
+                            Err(e) => println!("Couldn't write input recording, error: {}", e),
+                        }
+                    }
+
+                    if let Some(path) = &trace_path{
+                        match stats.write_csv(path){
+                            Ok(()) => println!("Frame stats written to: {:?}", path),
+                            Err(e) => println!("Couldn't write frame stats, error: {}", e),
+                        }
+                    }
+                },
                 Event::RedrawRequested(_) => {
-                    renderer.render();
-                    frames += 1;
+                    renderer.render(alpha);
+                    stats.record_frame();
+
+                    if let Some(recorder) = &mut controls.sequence{
+                        let result = futures::executor::block_on(renderer.capture())
+                            .and_then(|img| recorder.write_frame(&img));
+                        // a frame that can't be written, like one of a new size, would just be retried forever
+                        if let Err(e) = result{
+                            println!("Couldn't record frame, error: {}", e);
+                            let recorder = controls.sequence.take().unwrap();
+                            let written = recorder.get_written();
+                            match recorder.finish(){
+                                Ok(()) => println!("Recording stopped after {} frames", written),
+                                Err(e) => println!("Couldn't finish recording, error: {}", e),
+                            }
+                        }
+                    }
+
+                    if controls.sequence.as_ref().map_or(false, |r| r.is_finished()){
+                        let recorder = controls.sequence.take().unwrap();
+                        let written = recorder.get_written();
+                        match recorder.finish(){
+                            Ok(()) => println!("Recorded {} frames", written),
+                            Err(e) => println!("Couldn't finish recording, error: {}", e),
+                        }
+                    }
                 },
                 Event::MainEventsCleared => {
-                    timer.reset();
+                    // while recording the simulation moves one capture frame per redraw, not by the wall clock
+                    match &controls.sequence{
+                        Some(recorder) => controls.timer.reset_with_delta(recorder.frame_step()),
+                        None => controls.timer.reset(),
+                    }
+
+                    if let Some(replay) = &mut replay{
+                        for recorded in replay.next_events(controls.timer.get_tick()){
+                            apply_event(recorded.event, &window, &mut input, &mut renderer, &mut controls);
+                        }
+                    }
+
+                    while controls.timer.should_update(){
+                        let start = std::time::Instant::now();
+                        renderer.update(controls.timer.get_step().as_secs_f32(), &input);
+                        input.end_tick();
+                        controls.timer.update();
+                        stats.record_update(start.elapsed());
+
+                        if let Some(replay) = &mut replay{
+                            for recorded in replay.next_events(controls.timer.get_tick()){
+                                apply_event(recorded.event, &window, &mut input, &mut renderer, &mut controls);
+                            }
+                        }
+                    }
+
+                    if replay.as_ref().map_or(false, |r| r.is_finished()){
+                        println!("Input replay finished at tick {}", controls.timer.get_tick());
+                        replay = None;
+                    }
 
-                    while timer.should_update(){
-                        renderer.update(timer.get_delta().as_secs_f32());
-                        timer.update();
-                        updates += 1;
+                    // the fixed ticks are stopped, so the camera is moved once per frame by the wall clock instead
+                    if controls.debug_pause && controls.state == GameState::Playing{
+                        let dt = controls.timer.get_delta().min(timer::DEFAULT_MAX_DELTA);
+                        renderer.update(dt.as_secs_f32(), &input);
+                        input.end_tick();
                     }
 
+                    // a debug paused camera is already where this frame's update left it
+                    alpha = if controls.debug_pause { 1. } else { controls.timer.get_alpha() };
+
                     window.request_redraw();
                 },
-                Event::DeviceEvent { ref event, .. } => match event{
-                    DeviceEvent::MouseMotion { delta } => {
-                        let (dx, dy) = delta;
-                        renderer.get_camera().mouse_update(*dx as f32, *dy as f32);
-                        let size = window.inner_size();
-                        if let Err(e) = window.set_cursor_position(winit::dpi::PhysicalPosition::new(size.width / 2, size.height / 2)){
-                            println!("Couldn't set cursor position, error: {}", e);
+                Event::DeviceEvent { ref event, .. } => {
+                    let event = match event{
+                        DeviceEvent::MouseMotion { delta } if controls.state == GameState::Playing => {
+                            let size = window.inner_size();
+                            if let Err(e) = window.set_cursor_position(winit::dpi::PhysicalPosition::new(size.width / 2, size.height / 2)){
+                                println!("Couldn't set cursor position, error: {}", e);
+                            }
+                            Some(InputEvent::MouseMotion(delta.0, delta.1))
+                        },
+                        DeviceEvent::MouseWheel { delta } => {
+                            let dy = match delta{
+                                MouseScrollDelta::LineDelta(_, y) => *y,
+                                MouseScrollDelta::PixelDelta(p) => p.y as f32,
+                            };
+
+                            if dy > 0. {
+                                Some(InputEvent::Wheel(Binding::WheelUp))
+                            }else if dy < 0. {
+                                Some(InputEvent::Wheel(Binding::WheelDown))
+                            }else{
+                                None
+                            }
+                        },
+                        _ => None,
+                    };
+
+                    if let (Some(event), None) = (event, &replay){
+                        if let Some(recording) = &mut recording{
+                            recording.push(controls.timer.get_tick(), event);
                         }
-                    },
-                    DeviceEvent::MouseWheel { delta } => {
-                        let (_, dy) = match delta{
-                            MouseScrollDelta::LineDelta(x, y) => (*x, *y),
-                            MouseScrollDelta::PixelDelta(p) => (p.x as f32, p.y as f32),
-                        };
-
-                        renderer.get_camera().fovy -= dy.to_radians() * 2.;
-                        renderer.get_camera().fovy = renderer.get_camera().fovy.max(45.).min(120.);
-                    },
-                    _ => (),
+                        apply_event(event, &window, &mut input, &mut renderer, &mut controls);
+                    }
                 },
                 Event::WindowEvent {
                     ref event,
                     window_id,
-                } if window_id == window.id() => match event {
-                    WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
-                    WindowEvent::Resized(physical_size) => {
-                        renderer.resize(*physical_size);
-                    },
-                    WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
-                        renderer.resize(**new_inner_size);
-                    },
-                    WindowEvent::KeyboardInput {
-                        input,
-                        ..
-                    } => {
-                        match input {
-                            KeyboardInput {
+                } if window_id == window.id() => {
+                    let event = match event {
+                        WindowEvent::CloseRequested => {
+                            *control_flow = ControlFlow::Exit;
+                            None
+                        },
+                        WindowEvent::Resized(physical_size) => {
+                            renderer.resize(*physical_size);
+                            None
+                        },
+                        WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                            renderer.resize(**new_inner_size);
+                            None
+                        },
+                        WindowEvent::KeyboardInput {
+                            input: KeyboardInput {
                                 virtual_keycode: Some(keycode),
+                                state,
                                 ..
-                            } => {
-                                let velocity = 20.;
-                                match keycode{
-                                    VirtualKeyCode::Escape => running = false,
-                                    VirtualKeyCode::W => renderer.camera.velocity.set_z(-velocity),
-                                    VirtualKeyCode::S => renderer.camera.velocity.set_z(velocity),
-                                    VirtualKeyCode::A => renderer.camera.velocity.set_x(-velocity),
-                                    VirtualKeyCode::D => renderer.camera.velocity.set_x(velocity),
-                                    VirtualKeyCode::Space => renderer.camera.velocity.set_y(velocity),
-                                    VirtualKeyCode::LShift => renderer.camera.velocity.set_y(-velocity),
-                                    _ => (),
-                                }
                             },
-                            _ => {}
+                            ..
+                        } => Some(binding_event(Binding::Key(*keycode), *state)),
+                        WindowEvent::MouseInput { state, button, .. } => Some(binding_event(Binding::Mouse(*button), *state)),
+                        WindowEvent::Focused(false) => Some(InputEvent::FocusLost),
+                        _ => None,
+                    };
+
+                    if let (Some(event), None) = (event, &replay){
+                        if let Some(recording) = &mut recording{
+                            recording.push(controls.timer.get_tick(), event);
                         }
+                        apply_event(event, &window, &mut input, &mut renderer, &mut controls);
                     }
-                    _ => {}
                 },
                 _ => {}
             }
@@ -166,7 +313,220 @@ impl Game{
     }
 }
 
+// REVERSE_Z switches to a reverse-Z projection with an infinite far plane
+fn depth_mode() -> DepthMode{
+    match std::env::var_os("REVERSE_Z"){
+        Some(_) => DepthMode::ReverseInfinite,
+        None => DepthMode::Standard,
+    }
+}
+
+// SCENE picks the scene file to start with
+fn load_start_scene(renderer: &mut Renderer){
+    let scene_path = std::env::var_os("SCENE")
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(|| std::path::PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/res/scenes/default.ron")));
+    if let Err(e) = SceneFile::load(&scene_path).and_then(|file| renderer.load_scene(&file)){
+        println!("Couldn't load scene {:?}, error: {}", scene_path, e);
+    }
+}
+
+// HEADLESS=WxH renders the start scene once without a window and saves it to screenshots/
+fn render_headless(size: &str) -> Result<std::path::PathBuf, anyhow::Error>{
+    let mut dims = size.splitn(2, 'x').map(|v| v.trim().parse::<u32>().ok());
+    let (width, height) = match (dims.next().flatten(), dims.next().flatten()){
+        (Some(w), Some(h)) if w > 0 && h > 0 => (w, h),
+        _ => anyhow::bail!("Expected a size like 1280x720, got: {}", size),
+    };
+
+    let mut renderer = block_on(Renderer::new_headless(winit::dpi::PhysicalSize::new(width, height), depth_mode()));
+    load_start_scene(&mut renderer);
+    renderer.render(1.);
+
+    let path = std::path::PathBuf::from(format!("screenshots/headless_{}.png", unix_secs()));
+    renderer.screenshot(&path)?;
+    Ok(path)
+}
+
+fn unix_secs() -> u64{
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn binding_event(binding: Binding, state: ElementState) -> InputEvent{
+    match state{
+        ElementState::Pressed => InputEvent::Press(binding),
+        ElementState::Released => InputEvent::Release(binding),
+    }
+}
+
+// state the one-shot actions can change, bundled so it can be handed around the event loop
+struct Controls{
+    timer: timer::Timer,
+    state: GameState,
+    sequence: Option<SequenceRecorder>,
+    // keyframes added at runtime, played back or saved with the path actions
+    path: CameraPath,
+    // stops the simulation but not the camera or the cursor grab, for looking around a frozen frame
+    debug_pause: bool,
+    running: bool,
+}
+
+impl Controls{
+    fn set_state(&mut self, next: GameState, window: &Window, renderer: &mut Renderer){
+        self.state.transition(next, window, &mut self.timer, renderer);
+        // leaving Paused or Menu resumes the timer, a debug pause outlasts them
+        if self.debug_pause{
+            self.timer.pause();
+        }
+    }
+
+    fn toggle_debug_pause(&mut self){
+        self.debug_pause = !self.debug_pause;
+        if self.debug_pause{
+            self.timer.pause();
+        }else{
+            self.timer.resume();
+        }
+    }
+}
+
+fn apply_event(event: InputEvent, window: &Window, input: &mut InputState, renderer: &mut Renderer, controls: &mut Controls){
+    match event{
+        InputEvent::Press(binding) => {
+            if input.press(binding){
+                for action in input.map.actions(binding){
+                    handle_action(action, window, renderer, controls);
+                }
+            }
+        },
+        InputEvent::Release(binding) => input.release(binding),
+        InputEvent::Wheel(binding) => {
+            for action in input.map.actions(binding){
+                handle_action(action, window, renderer, controls);
+            }
+        },
+        InputEvent::MouseMotion(dx, dy) => {
+            if controls.state == GameState::Playing{
+                renderer.mouse_motion(dx as f32, dy as f32, input);
+            }
+        },
+        InputEvent::FocusLost => {
+            input.release_all();
+            if controls.state == GameState::Playing{
+                controls.set_state(GameState::Paused, window, renderer);
+            }
+        },
+    }
+}
+
+// one-shot actions, movement is polled from the InputState every tick
+fn handle_action(action: Action, window: &Window, renderer: &mut Renderer, controls: &mut Controls){
+    let timer = &mut controls.timer;
+    match action{
+        Action::ZoomIn => renderer.zoom(1.),
+        Action::ZoomOut => renderer.zoom(-1.),
+        Action::Pause => match controls.state{
+            GameState::Playing => controls.set_state(GameState::Paused, window, renderer),
+            GameState::Paused => controls.set_state(GameState::Playing, window, renderer),
+            GameState::Menu => (),
+        },
+        Action::DebugPause if controls.state == GameState::Playing => controls.toggle_debug_pause(),
+        Action::Menu => match controls.state{
+            GameState::Menu => controls.set_state(GameState::Playing, window, renderer),
+            _ => controls.set_state(GameState::Menu, window, renderer),
+        },
+        Action::SwitchCamera => println!("Camera: {}", renderer.next_controller()),
+        Action::ToggleProjection => renderer.get_camera().toggle_projection(),
+        Action::Step => timer.single_step(),
+        Action::SlowDown => timer.set_scale(timer.get_scale() * 0.5),
+        Action::SpeedUp => timer.set_scale(timer.get_scale() * 2.),
+        Action::ResetSpeed => timer.set_scale(1.),
+        Action::RecordSequence => {
+            let sequence = &mut controls.sequence;
+            if let Some(recorder) = sequence.take(){
+                let written = recorder.get_written();
+                match recorder.finish(){
+                    Ok(()) => println!("Recording stopped after {} frames", written),
+                    Err(e) => println!("Couldn't finish recording, error: {}", e),
+                }
+                return;
+            }
+
+            // SEQUENCE_FORMAT=y4m|png, SEQUENCE_FPS and SEQUENCE_FRAMES configure the capture
+            let env_or = |key: &str, default: u32| std::env::var(key).ok().and_then(|v| v.parse().ok()).unwrap_or(default);
+            let fps = env_or("SEQUENCE_FPS", 60);
+            let frames = env_or("SEQUENCE_FRAMES", 300);
+            let (format, path) = match std::env::var("SEQUENCE_FORMAT").as_ref().map(|s| s.as_str()){
+                Ok("y4m") => (SequenceFormat::Y4m, format!("recordings/{}.y4m", unix_secs())),
+                _ => (SequenceFormat::Png, format!("recordings/{}", unix_secs())),
+            };
+
+            match SequenceRecorder::new(std::path::Path::new(&path), format, fps, frames, timer.get_max_frame()){
+                Ok(recorder) => {
+                    println!("Recording {} frames at {} FPS to: {:?}", frames, fps, path);
+                    *sequence = Some(recorder);
+                },
+                Err(e) => println!("Couldn't start recording, error: {}", e),
+            }
+        },
+        Action::AddKeyframe => {
+            controls.path.push(Keyframe::from_camera(&renderer.camera));
+            println!("Camera keyframe {} added", controls.path.len());
+        },
+        Action::SavePath => {
+            let path = std::path::PathBuf::from(format!("paths/{}.ron", unix_secs()));
+            match controls.path.save(&path){
+                Ok(()) => println!("Camera path saved to: {:?}", path),
+                Err(e) => println!("Couldn't save camera path, error: {}", e),
+            }
+        },
+        // plays the recorded keyframes, or the file in CAMERA_PATH when fewer than two were added
+        Action::PlayPath if renderer.is_playing_path() => renderer.stop_path(),
+        Action::PlayPath => {
+            let path = match std::env::var_os("CAMERA_PATH"){
+                Some(file) if controls.path.len() < 2 => CameraPath::load(std::path::Path::new(&file)),
+                _ => Ok(controls.path.clone()),
+            };
+            match path{
+                Ok(path) if path.len() > 0 => {
+                    println!("Playing camera path of {} keyframes over {}s", path.len(), path.duration);
+                    renderer.play_path(path);
+                },
+                Ok(_) => println!("No camera keyframes to play"),
+                Err(e) => println!("Couldn't load camera path, error: {}", e),
+            }
+        },
+        Action::SaveScene => {
+            let path = std::path::Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/res/scenes/saved.ron"));
+            match renderer.save_scene().save(path){
+                Ok(()) => println!("Scene saved to: {:?}", path),
+                Err(e) => println!("Couldn't save scene, error: {}", e),
+            }
+        },
+        Action::Screenshot => {
+            let path = std::path::PathBuf::from(format!("screenshots/{}.png", unix_secs()));
+            match renderer.screenshot(&path){
+                Ok(()) => println!("Screenshot saved to: {:?}", path),
+                Err(e) => println!("Couldn't save screenshot, error: {}", e),
+            }
+        },
+        Action::Quit if controls.state == GameState::Menu => controls.running = false,
+        _ => (),
+    }
+}
+
 fn main() {
+    if let Ok(size) = std::env::var("HEADLESS"){
+        match render_headless(&size){
+            Ok(path) => println!("Headless frame saved to: {:?}", path),
+            Err(e) => println!("Couldn't render headless frame, error: {}", e),
+        }
+        return;
+    }
+
     let game = Game::new();
     game.run();
 }
@@ -0,0 +1,154 @@
+use serde::{Serialize, Deserialize};
+use crate::texture::Texture;
+use glam::Vec3;
+use crate::frustum::Aabb;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct Vertex{
+    pub position: [f32; 3],
+    pub tex_coord: [f32; 2],
+}
+
+impl Vertex{
+    pub fn desc<'a>() -> wgpu::VertexBufferDescriptor<'a>{
+        use std::mem;
+        wgpu::VertexBufferDescriptor{
+            stride: mem::size_of::<Vertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::InputStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttributeDescriptor {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float3,
+                },
+                wgpu::VertexAttributeDescriptor {
+                    offset: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float2,
+                },
+            ]
+        }
+    }
+}
+
+unsafe impl bytemuck::Pod for Vertex {}
+unsafe impl bytemuck::Zeroable for Vertex {}
+
+// per-instance model matrix, fed through a second vertex buffer at locations 2 to 5
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct Instance{
+    pub model: [[f32; 4]; 4],
+}
+
+impl Instance{
+    pub fn desc<'a>() -> wgpu::VertexBufferDescriptor<'a>{
+        use std::mem;
+        wgpu::VertexBufferDescriptor{
+            stride: mem::size_of::<Instance>() as wgpu::BufferAddress,
+            step_mode: wgpu::InputStepMode::Instance,
+            attributes: &[
+                wgpu::VertexAttributeDescriptor {
+                    offset: 0,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float4,
+                },
+                wgpu::VertexAttributeDescriptor {
+                    offset: mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float4,
+                },
+                wgpu::VertexAttributeDescriptor {
+                    offset: mem::size_of::<[f32; 4]>() as wgpu::BufferAddress * 2,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float4,
+                },
+                wgpu::VertexAttributeDescriptor {
+                    offset: mem::size_of::<[f32; 4]>() as wgpu::BufferAddress * 3,
+                    shader_location: 5,
+                    format: wgpu::VertexFormat::Float4,
+                },
+            ]
+        }
+    }
+}
+
+unsafe impl bytemuck::Pod for Instance {}
+unsafe impl bytemuck::Zeroable for Instance {}
+
+// keeps the CPU side data around so the scene can be saved back out
+pub struct Mesh{
+    pub name: String,
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u16>,
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    pub indices_len: u32,
+    // in model space, for culling
+    pub bounds: Aabb,
+}
+
+impl Mesh{
+    pub fn new(device: &wgpu::Device, name: &str, vertices: &[Vertex], indices: &[u16]) -> Self{
+        let vertex_buffer = device.create_buffer_with_data(
+            bytemuck::cast_slice(vertices),
+            wgpu::BufferUsage::VERTEX,
+        );
+
+        let index_buffer = device.create_buffer_with_data(
+            bytemuck::cast_slice(indices),
+            wgpu::BufferUsage::INDEX,
+        );
+
+        let bounds = Aabb::from_points(vertices.iter().map(|v| Vec3::from(v.position)))
+            .unwrap_or_else(|| Aabb::new(Vec3::zero(), Vec3::zero()));
+
+        Self{
+            name: name.to_string(),
+            vertices: vertices.to_vec(),
+            indices: indices.to_vec(),
+            vertex_buffer,
+            index_buffer,
+            indices_len: indices.len() as u32,
+            bounds,
+        }
+    }
+}
+
+pub struct Material{
+    pub name: String,
+    // file name the texture was loaded from, inside res/img
+    pub source: String,
+    // multiplied with the texture, uploaded per draw
+    pub tint: [f32; 4],
+    pub texture: Texture,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl Material{
+    pub fn new(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, name: &str, source: &str, texture: Texture) -> Self{
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor{
+            layout,
+            label: Some("material bind group"),
+            bindings: &[
+                wgpu::Binding {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::Binding{
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                },
+            ],
+        });
+
+        Self{
+            name: name.to_string(),
+            source: source.to_string(),
+            tint: [1., 1., 1., 1.],
+            texture,
+            bind_group,
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use glam::Vec3;
+use crate::camera::{Camera, Projection};
+use crate::controller::CameraController;
+use crate::input::{InputState, Action};
+
+const SENSITIVITY: f32 = 0.25;
+// fraction of the distance moved per pixel, so panning feels the same at any zoom
+const PAN_SPEED: f32 = 0.002;
+const ZOOM_FACTOR: f32 = 1.1;
+const MIN_DISTANCE: f32 = 0.1;
+const PITCH_LIMIT: f32 = 89.;
+
+pub const DEFAULT_DISTANCE: f32 = 4.;
+
+// arcball style camera that circles `focus`, it only moves the Camera so the uniforms stay the same
+#[derive(Copy, Clone, Debug)]
+pub struct Orbit{
+    pub focus: Vec3,
+    pub distance: f32,
+    // degrees, same convention as Camera::yaw and Camera::pitch
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl Orbit{
+    // orbits the point `distance` in front of the camera, so switching doesn't move the view
+    pub fn from_camera(camera: &Camera, distance: f32) -> Self{
+        Self{
+            focus: camera.eye + camera.target * distance,
+            distance,
+            yaw: camera.yaw,
+            pitch: camera.pitch.max(-PITCH_LIMIT).min(PITCH_LIMIT),
+        }
+    }
+
+    pub fn rotate(&mut self, dx: f32, dy: f32){
+        self.yaw += dx * SENSITIVITY;
+        self.pitch = (self.pitch - dy * SENSITIVITY).max(-PITCH_LIMIT).min(PITCH_LIMIT);
+    }
+
+    // moves the focus in the view plane, the scene follows the mouse
+    pub fn pan(&mut self, camera: &Camera, dx: f32, dy: f32){
+        let right = camera.target.cross(camera.up).normalize();
+        let up = camera.up.normalize();
+        self.focus += (right * -dx + up * dy) * self.distance * PAN_SPEED;
+    }
+
+    pub fn apply(&self, camera: &mut Camera){
+        camera.look(self.yaw, self.pitch, 0.);
+        camera.eye = self.focus - camera.target * self.distance;
+        // orbiting follows the mouse directly, there is nothing to interpolate
+        camera.save_previous();
+    }
+}
+
+impl CameraController for Orbit{
+    fn name(&self) -> &str{
+        "orbit"
+    }
+
+    fn reset(&mut self, camera: &Camera){
+        *self = Self::from_camera(camera, DEFAULT_DISTANCE);
+    }
+
+    fn activate(&mut self, camera: &mut Camera){
+        self.apply(camera);
+    }
+
+    // drag to rotate, hold the pan modifier while dragging to pan
+    fn mouse_motion(&mut self, camera: &mut Camera, dx: f32, dy: f32, input: &InputState){
+        if !input.is_action_held(Action::Orbit){
+            return;
+        }
+
+        if input.is_action_held(Action::Pan){
+            self.pan(camera, dx, dy);
+        }else{
+            self.rotate(dx, dy);
+        }
+        self.apply(camera);
+    }
+
+    // moves closer instead of narrowing the fov, distance doesn't change an orthographic view so that scales its height
+    fn zoom(&mut self, camera: &mut Camera, steps: f32){
+        if camera.projection == Projection::Orthographic{
+            camera.height = (camera.height * ZOOM_FACTOR.powf(-steps)).max(MIN_DISTANCE);
+            return;
+        }
+        self.distance = (self.distance * ZOOM_FACTOR.powf(-steps)).max(MIN_DISTANCE);
+        self.apply(camera);
+    }
+
+    fn update(&mut self, camera: &mut Camera, _dt: f32, _input: &InputState){
+        self.apply(camera);
+    }
+}
@@ -0,0 +1,169 @@
+use std::path::Path;
+use serde::{Serialize, Deserialize};
+use crate::camera::Camera;
+use crate::controller::CameraController;
+use crate::input::InputState;
+
+// seconds a recorded path spends between two keyframes, the file can change it afterwards
+pub const SECONDS_PER_KEYFRAME: f32 = 2.;
+
+// degrees, same as CameraDesc
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct Keyframe{
+    pub eye: [f32; 3],
+    pub yaw: f32,
+    pub pitch: f32,
+    pub fovy: f32,
+}
+
+impl Keyframe{
+    pub fn from_camera(camera: &Camera) -> Self{
+        Self{
+            eye: camera.eye.into(),
+            yaw: camera.yaw,
+            pitch: camera.pitch,
+            fovy: camera.fovy.to_degrees(),
+        }
+    }
+
+    fn to_array(&self) -> [f32; 6]{
+        [self.eye[0], self.eye[1], self.eye[2], self.yaw, self.pitch, self.fovy]
+    }
+
+    fn from_array(a: [f32; 6]) -> Self{
+        Self{
+            eye: [a[0], a[1], a[2]],
+            yaw: a[3],
+            pitch: a[4],
+            fovy: a[5],
+        }
+    }
+}
+
+fn catmull_rom(p0: f32, p1: f32, p2: f32, p3: f32, u: f32) -> f32{
+    let u2 = u * u;
+    let u3 = u2 * u;
+    0.5 * (2. * p1
+        + (p2 - p0) * u
+        + (2. * p0 - 5. * p1 + 4. * p2 - p3) * u2
+        + (3. * p1 - p0 - 3. * p2 + p3) * u3)
+}
+
+// keyframes spaced evenly over `duration` seconds
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct CameraPath{
+    pub duration: f32,
+    pub keyframes: Vec<Keyframe>,
+}
+
+impl CameraPath{
+    pub fn new() -> Self{
+        Self::default()
+    }
+
+    // yaw is unwrapped against the previous keyframe so the spline takes the short way around
+    pub fn push(&mut self, mut keyframe: Keyframe){
+        if let Some(last) = self.keyframes.last(){
+            let turn = (keyframe.yaw - last.yaw + 180.).rem_euclid(360.) - 180.;
+            keyframe.yaw = last.yaw + turn;
+        }
+        self.keyframes.push(keyframe);
+        self.duration = (self.keyframes.len() - 1) as f32 * SECONDS_PER_KEYFRAME;
+    }
+
+    pub fn len(&self) -> usize{
+        self.keyframes.len()
+    }
+
+    // `t` from 0 to 1 over the whole path, the end keyframes are repeated to get the outer control points
+    pub fn sample(&self, t: f32) -> Option<Keyframe>{
+        let last = self.keyframes.len().checked_sub(1)?;
+        if last == 0{
+            return Some(self.keyframes[0]);
+        }
+
+        let pos = t.max(0.).min(1.) * last as f32;
+        let i = (pos.floor() as usize).min(last - 1);
+        let u = pos - i as f32;
+
+        let p0 = self.keyframes[i.saturating_sub(1)].to_array();
+        let p1 = self.keyframes[i].to_array();
+        let p2 = self.keyframes[i + 1].to_array();
+        let p3 = self.keyframes[(i + 2).min(last)].to_array();
+
+        let mut out = [0.; 6];
+        for c in 0..6{
+            out[c] = catmull_rom(p0[c], p1[c], p2[c], p3[c], u);
+        }
+        Some(Keyframe::from_array(out))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, anyhow::Error>{
+        let src = std::fs::read_to_string(path)?;
+        let camera_path = ron::de::from_str(&src)?;
+        Ok(camera_path)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), anyhow::Error>{
+        let src = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        if let Some(parent) = path.parent(){
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, src)?;
+        Ok(())
+    }
+}
+
+// plays a CameraPath once, advanced by the fixed ticks so pausing and time scale apply to it too
+pub struct PathPlayer{
+    path: CameraPath,
+    elapsed: f32,
+}
+
+impl PathPlayer{
+    pub fn new(path: CameraPath) -> Self{
+        Self{
+            path,
+            elapsed: 0.,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool{
+        self.elapsed >= self.path.duration
+    }
+
+    fn apply(&self, camera: &mut Camera){
+        let t = if self.path.duration > 0. { self.elapsed / self.path.duration } else { 1. };
+        if let Some(keyframe) = self.path.sample(t){
+            camera.eye = keyframe.eye.into();
+            camera.look(keyframe.yaw, keyframe.pitch, 0.);
+            camera.fovy = keyframe.fovy.to_radians();
+        }
+    }
+}
+
+impl CameraController for PathPlayer{
+    fn name(&self) -> &str{
+        "path"
+    }
+
+    // restarts, the path itself is the state
+    fn reset(&mut self, _camera: &Camera){
+        self.elapsed = 0.;
+    }
+
+    fn activate(&mut self, camera: &mut Camera){
+        self.apply(camera);
+        camera.save_previous();
+    }
+
+    fn mouse_motion(&mut self, _camera: &mut Camera, _dx: f32, _dy: f32, _input: &InputState){}
+
+    fn zoom(&mut self, _camera: &mut Camera, _steps: f32){}
+
+    fn update(&mut self, camera: &mut Camera, dt: f32, _input: &InputState){
+        camera.save_previous();
+        self.elapsed = (self.elapsed + dt).min(self.path.duration.max(0.));
+        self.apply(camera);
+    }
+}
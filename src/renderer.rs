@@ -1,46 +1,23 @@
-use crate::camera::{Camera};
+use crate::camera::{Camera, DepthMode, Projection};
+use crate::controller::{CameraController, Fly, Walk, Turntable};
 // use cgmath::{Vec3, Mat4, SquareMatrix};
-use glam::{Vec3, Mat4};
+use glam::{Vec3, Vec4, Quat, Mat4};
 use winit::window::Window;
 use std::path::Path;
 use crate::texture::Texture;
-
-#[repr(C)]
-#[derive(Copy, Clone, Debug)]
-struct Vertex{
-    position: [f32; 3],
-    tex_coord: [f32; 2],
-}
-
-impl Vertex{
-    fn desc<'a>() -> wgpu::VertexBufferDescriptor<'a>{
-        use std::mem;
-        wgpu::VertexBufferDescriptor{
-            stride: mem::size_of::<Vertex>() as wgpu::BufferAddress,
-            step_mode: wgpu::InputStepMode::Vertex,
-            attributes: &[
-                wgpu::VertexAttributeDescriptor {
-                    offset: 0,
-                    shader_location: 0,
-                    format: wgpu::VertexFormat::Float3,
-                },
-                wgpu::VertexAttributeDescriptor {
-                    offset: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
-                    shader_location: 1,
-                    format: wgpu::VertexFormat::Float2,
-                },
-            ]
-        }
-    }
-}
-
-unsafe impl bytemuck::Pod for Vertex {}
-unsafe impl bytemuck::Zeroable for Vertex {}
+use crate::input::InputState;
+use crate::capture;
+use crate::frustum::Frustum;
+use crate::orbit::{self, Orbit};
+use crate::path::{CameraPath, PathPlayer};
+use crate::staging::{self, StagingBelt};
+use crate::mesh::{Vertex, Instance, Mesh, Material};
+use crate::scene::{Scene, Entity, Light, Transform, MeshId, MaterialId};
+use crate::scene_file::{SceneFile, CameraDesc, MeshDesc, MaterialDesc, LightDesc, EntityDesc};
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug)]
 pub struct Uniforms{
-    model: Mat4,
     view: Mat4,
     projection: Mat4,
 }
@@ -48,7 +25,6 @@ pub struct Uniforms{
 impl Uniforms{
     pub fn new() -> Self{
         Self{
-            model: Mat4::from_translation(Vec3::new(0., 0., 0.1)),
             view: Mat4::identity(),
             projection: Mat4::identity(),
         }
@@ -59,14 +35,27 @@ impl Uniforms{
         self.projection = camera.get_projection();
     }
 
-    pub fn update_model(&mut self, model: Vec3){
-        self.model = Mat4::from_translation(model);
+    pub fn update_interpolated_view(&mut self, camera: &Camera, alpha: f32){
+        self.view = camera.get_interpolated_view(alpha);
+        self.projection = camera.get_interpolated_projection(alpha);
     }
 }
 
 unsafe impl bytemuck::Pod for Uniforms {}
 unsafe impl bytemuck::Zeroable for Uniforms {}
 
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct ObjectUniforms{
+    tint: [f32; 4],
+}
+
+unsafe impl bytemuck::Pod for ObjectUniforms {}
+unsafe impl bytemuck::Zeroable for ObjectUniforms {}
+
+// dynamic offsets have to be multiples of the device's min uniform buffer offset alignment, 256 covers every backend
+const OBJECT_UNIFORM_STRIDE: wgpu::BufferAddress = 256;
+
 fn glsl_to_spirv(path: &Path)-> (std::vec::Vec<u32>, std::vec::Vec<u32>) {
     println!("Loading shaders at: {:?}", &path);
     let vertex_src = std::fs::read_to_string(path.join("shader.vert")).expect("Couldn't load vertex shader");
@@ -90,25 +79,53 @@ fn glsl_to_spirv(path: &Path)-> (std::vec::Vec<u32>, std::vec::Vec<u32>) {
     (vertex, fragment)
 }
 
+// entities sharing a mesh and material, drawn with one instanced call
+struct Batch{
+    mesh: MeshId,
+    material: MaterialId,
+    instances: std::ops::Range<u32>,
+    // into the object uniform buffer
+    offset: wgpu::DynamicOffset,
+}
+
+// where the screen pass ends up, a window's swap chain or a texture we own
+pub enum Target{
+    Window{
+        surface: wgpu::Surface,
+        swap_chain: wgpu::SwapChain,
+    },
+    Offscreen(Texture),
+}
+
 #[allow(dead_code)]
 pub struct Renderer {
-    surface: wgpu::Surface,
+    target: Target,
     adapter: wgpu::Adapter,
     device: wgpu::Device,
     queue: wgpu::Queue,
     pub sc_desc: wgpu::SwapChainDescriptor,
-    swap_chain: wgpu::SwapChain,
 
-    img_tex: Texture,
-    vertex_buffer: wgpu::Buffer,
-    index_buffer: wgpu::Buffer,
+    meshes: Vec<Mesh>,
+    materials: Vec<Material>,
+    pub scene: Scene,
+
+    instance_buffer: wgpu::Buffer,
+    instance_capacity: usize,
+    batches: Vec<Batch>,
+
+    object_bind_group_layout: wgpu::BindGroupLayout,
+    object_buffer: wgpu::Buffer,
+    object_bind_group: wgpu::BindGroup,
+    object_capacity: usize,
+
+    staging: StagingBelt,
 
     uniforms: Uniforms,
     uniform_buffer: wgpu::Buffer,
     uniform_bind_group: wgpu::BindGroup,
 
     depth_tex: Texture,
-    opaque_bind_group: wgpu::BindGroup,
+    texture_bind_group_layout: wgpu::BindGroupLayout,
     opaque_pipeline: wgpu::RenderPipeline,
 
     accum_tex: Texture,
@@ -119,21 +136,34 @@ pub struct Renderer {
     screen_bind_group: wgpu::BindGroup,
     screen_pipeline: wgpu::RenderPipeline,
 
+    overlay_pipeline: wgpu::RenderPipeline,
+    pub overlay: bool,
+
+    // swap chain frames can't be read back, so windows composite a copy in here, `None` when offscreen
+    capture_target: Option<Texture>,
+    capture_buffer: wgpu::Buffer,
+
     pub camera: Camera,
+    controllers: Vec<Box<dyn CameraController>>,
+    controller: usize,
+    // takes over from the active controller until the path ends
+    playback: Option<PathPlayer>,
+
+    // entities drawn and skipped by the last render
+    visible: usize,
+    culled: usize,
     size: winit::dpi::PhysicalSize<u32>,
-    indices_len: u32,
 }
 
 #[allow(dead_code)]
 impl Renderer {
-    pub async fn new(window: &Window) -> Self {
-        let size = window.inner_size();
-        let surface = wgpu::Surface::create(window);
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Bgra8UnormSrgb;
 
+    async fn request_device(surface: Option<&wgpu::Surface>) -> (wgpu::Adapter, wgpu::Device, wgpu::Queue){
         let adapter = wgpu::Adapter::request(
             &wgpu::RequestAdapterOptions {
                 power_preference: wgpu::PowerPreference::Default,
-                compatible_surface: Some(&surface),
+                compatible_surface: surface,
             },
             wgpu::BackendBit::PRIMARY,
         ).await.expect("Couldn't request the Adapter");
@@ -145,28 +175,70 @@ impl Renderer {
             limits: Default::default(),
         }).await;
 
-        let sc_desc = wgpu::SwapChainDescriptor{
+        (adapter, device, queue)
+    }
+
+    fn target_desc(size: winit::dpi::PhysicalSize<u32>) -> wgpu::SwapChainDescriptor{
+        wgpu::SwapChainDescriptor{
             usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT,
-            format: wgpu::TextureFormat::Bgra8UnormSrgb,
+            format: Self::FORMAT,
             width: size.width,
             height: size.height,
             present_mode: wgpu::PresentMode::Fifo,
-        };
+        }
+    }
 
+    pub async fn new(window: &Window, depth: DepthMode) -> Self {
+        let size = window.inner_size();
+        let surface = wgpu::Surface::create(window);
+        let (adapter, device, queue) = Self::request_device(Some(&surface)).await;
+
+        let sc_desc = Self::target_desc(size);
         let swap_chain = device.create_swap_chain(&surface, &sc_desc);
 
-        let camera = Camera {
+        Self::with_target(Target::Window{ surface, swap_chain }, adapter, device, queue, sc_desc, size, depth)
+    }
+
+    // renders into an offscreen texture of `size`, no window or surface needed
+    pub async fn new_headless(size: winit::dpi::PhysicalSize<u32>, depth: DepthMode) -> Self {
+        let (adapter, device, queue) = Self::request_device(None).await;
+
+        let sc_desc = Self::target_desc(size);
+        let target = Texture::create_target(&device, &sc_desc, "offscreen target");
+
+        Self::with_target(Target::Offscreen(target), adapter, device, queue, sc_desc, size, depth)
+    }
+
+    fn with_target(target: Target, adapter: wgpu::Adapter, device: wgpu::Device, queue: wgpu::Queue, sc_desc: wgpu::SwapChainDescriptor, size: winit::dpi::PhysicalSize<u32>, depth: DepthMode) -> Self {
+
+        let mut camera = Camera {
             eye: (0., 0., 2.).into(),
-            target: (0., 0., 0.1).into(),
+            prev_eye: (0., 0., 2.).into(),
+            prev_orientation: Quat::identity(),
+            prev_fovy: 90f32.to_radians(),
+            target: (0., 0., 1.).into(),
             up: Vec3::unit_y(),
             aspect: sc_desc.width as f32 / sc_desc.height as f32,
+            projection: Projection::Perspective,
             fovy: 90f32.to_radians(),
+            height: 4.,
             near: 0.1,
             far: 100.,
-            velocity: Vec3::new(0., 0., 0.),
+            depth,
             yaw: 0.,
             pitch: 0.,
+            roll: 0.,
+            orientation: Quat::identity(),
         };
+        camera.look(90., 0., 0.);
+        camera.save_previous();
+
+        let controllers: Vec<Box<dyn CameraController>> = vec![
+            Box::new(Fly::new(&camera)),
+            Box::new(Walk::new(&camera)),
+            Box::new(Orbit::from_camera(&camera, orbit::DEFAULT_DISTANCE)),
+            Box::new(Turntable::new(&camera)),
+        ];
 
         // ***************** MVP UBO LAYOUT *****************
         let uniform_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor{
@@ -205,7 +277,30 @@ impl Renderer {
             ],
         });
 
+        // ***************** PER-OBJECT UBO LAYOUT *****************
+        let object_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor{
+            label: Some("object bind group layout"),
+            bindings: &[
+            wgpu::BindGroupLayoutEntry{
+                binding: 0,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::UniformBuffer{
+                    dynamic: true,
+                },
+            }
+            ],
+        });
+
+        let object_capacity = 16;
+        let (object_buffer, object_bind_group) = Self::create_object_buffer(&device, &object_bind_group_layout, object_capacity);
+
         let depth_tex = Texture::create_depth(&device, &sc_desc, "depth texture");
+        let capture_target = match &target{
+            Target::Window{ .. } => Some(Texture::create_target(&device, &sc_desc, "capture target")),
+            Target::Offscreen(_) => None,
+        };
+        let capture_buffer = capture::create_buffer(&device, sc_desc.width, sc_desc.height);
+
         let opaque_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor{
             label: Some("texture bind group layout"),
             bindings: &[
@@ -228,28 +323,8 @@ impl Renderer {
             ],
         });
 
-        let img_path = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/res/img"));
-        let img_bytes = std::fs::read(img_path.join("glass.png")).expect("Couldn't read image");
-        let (img_tex, cmd_buffer) = Texture::from_bytes(&device, &img_bytes).expect("Couldn't load texture");
-        queue.submit(&[cmd_buffer]);
-
         // let opaque_out = Texture::create_empty(&device, &sc_desc, wgpu::TextureFormat::Rgba16Float, "opaque out tex");
 
-        let opaque_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor{
-            layout: &opaque_bind_group_layout,
-            label: Some("img tex bind group"),
-            bindings: &[
-                wgpu::Binding {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&img_tex.view),
-                },
-                wgpu::Binding{
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&img_tex.sampler),
-                },
-            ],
-        });
-
         let shader_path = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/shaders/opaque"));
         let (vs, fs) = glsl_to_spirv(shader_path);
         let vs_module = device.create_shader_module(&vs);
@@ -259,6 +334,7 @@ impl Renderer {
             bind_group_layouts: &[
                 &opaque_bind_group_layout,
                 &uniform_bind_group_layout,
+                &object_bind_group_layout,
             ],
         });
 
@@ -299,7 +375,7 @@ impl Renderer {
             depth_stencil_state: Some(wgpu::DepthStencilStateDescriptor{
                 format: Texture::DEPTH_FORMAT,
                 depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
+                depth_compare: depth.compare(),
                 stencil_front: wgpu::StencilStateFaceDescriptor::IGNORE,
                 stencil_back: wgpu::StencilStateFaceDescriptor::IGNORE,
                 stencil_read_mask: 0,
@@ -309,6 +385,7 @@ impl Renderer {
                 index_format: wgpu::IndexFormat::Uint16,
                 vertex_buffers: &[
                     Vertex::desc(),
+                    Instance::desc(),
                 ],
             },
             sample_count: 1,
@@ -369,6 +446,7 @@ impl Renderer {
             bind_group_layouts: &[
                 &opaque_bind_group_layout, //using opaque
                 &uniform_bind_group_layout,
+                &object_bind_group_layout,
             ],
         });
 
@@ -423,7 +501,7 @@ impl Renderer {
             depth_stencil_state: Some(wgpu::DepthStencilStateDescriptor{
                 format: Texture::DEPTH_FORMAT,
                 depth_write_enabled: false,
-                depth_compare: wgpu::CompareFunction::Less,
+                depth_compare: depth.compare(),
                 stencil_front: wgpu::StencilStateFaceDescriptor::IGNORE,
                 stencil_back: wgpu::StencilStateFaceDescriptor::IGNORE,
                 stencil_read_mask: 0,
@@ -433,6 +511,7 @@ impl Renderer {
                 index_format: wgpu::IndexFormat::Uint16,
                 vertex_buffers: &[
                     Vertex::desc(),
+                    Instance::desc(),
                 ],
             },
             sample_count: 1,
@@ -536,58 +615,96 @@ impl Renderer {
             alpha_to_coverage_enabled: false,
         });
 
-        // ***************** BUFFERS *****************
-        let vertices = &[
-
-            //Quad2
-            Vertex { position: [ 0.5, -0.5, 1.], tex_coord: [0., 1.], },
-            Vertex { position: [ 1.5, -0.5, 1.], tex_coord: [1., 1.], },
-            Vertex { position: [ 0.5,  0.5, 1.], tex_coord: [0., 0.], },
-            Vertex { position: [ 1.5,  0.5, 1.], tex_coord: [1., 0.], },
+        // ***************** OVERLAY PIPELINE *****************
+        let shader_path = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/shaders/overlay"));
+        let (vs, fs) = glsl_to_spirv(shader_path);
+        let vs_module = device.create_shader_module(&vs);
+        let fs_module = device.create_shader_module(&fs);
 
-            //Quad1
-            Vertex { position: [-0.5, -0.5, 0.], tex_coord: [0., 1.], },
-            Vertex { position: [ 0.5, -0.5, 0.], tex_coord: [1., 1.], },
-            Vertex { position: [-0.5,  0.5, 0.], tex_coord: [0., 0.], },
-            Vertex { position: [ 0.5,  0.5, 0.], tex_coord: [1., 0.], },
-        ];
-        let indices: &[u16] = &[
-            0, 1, 2,
-            2, 1, 3,
+        let overlay_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor{
+            bind_group_layouts: &[],
+        });
 
-            4, 5, 6,
-            6, 5, 7
-        ];
-        let indices_len = indices.len() as u32;
+        let overlay_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor{
+            layout: &overlay_pipeline_layout,
+            vertex_stage: wgpu::ProgrammableStageDescriptor{
+                module: &vs_module,
+                entry_point: "main",
+            },
+            fragment_stage: Some(wgpu::ProgrammableStageDescriptor{
+                module: &fs_module,
+                entry_point: "main",
+            }),
+            rasterization_state: Some(wgpu::RasterizationStateDescriptor{
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: wgpu::CullMode::Back,
+                depth_bias: 0,
+                depth_bias_slope_scale: 0.0,
+                depth_bias_clamp: 0.0,
+            }),
+            color_states: &[
+                wgpu::ColorStateDescriptor{
+                    format: sc_desc.format,
+                    color_blend: wgpu::BlendDescriptor{
+                        src_factor: wgpu::BlendFactor::SrcAlpha,
+                        dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                        operation: wgpu::BlendOperation::Add,
+                    },
+                    alpha_blend: wgpu::BlendDescriptor{
+                        src_factor: wgpu::BlendFactor::One,
+                        dst_factor: wgpu::BlendFactor::One,
+                        operation: wgpu::BlendOperation::Add,
+                    },
+                    write_mask: wgpu::ColorWrite::ALL,
+                },
+            ],
+            primitive_topology: wgpu::PrimitiveTopology::TriangleList,
+            depth_stencil_state: None,
+            vertex_state: wgpu::VertexStateDescriptor{
+                index_format: wgpu::IndexFormat::Uint16,
+                vertex_buffers: &[],
+            },
+            sample_count: 1,
+            sample_mask: !0,
+            alpha_to_coverage_enabled: false,
+        });
 
-        let vertex_buffer = device.create_buffer_with_data(
-            bytemuck::cast_slice(vertices),
-            wgpu::BufferUsage::VERTEX,
-        );
+        // meshes, materials and entities come from a scene file, see `load_scene`
+        let meshes = Vec::new();
+        let materials = Vec::new();
+        let scene = Scene::new();
 
-        let index_buffer = device.create_buffer_with_data(
-            bytemuck::cast_slice(indices),
-            wgpu::BufferUsage::INDEX,
-        );
+        let instance_capacity = 64;
+        let instance_buffer = Self::create_instance_buffer(&device, instance_capacity);
 
         Self{
-            surface,
+            target,
             adapter,
             device,
             queue,
             sc_desc,
-            swap_chain,
 
-            img_tex,
-            vertex_buffer,
-            index_buffer,
+            meshes,
+            materials,
+            scene,
+
+            instance_buffer,
+            instance_capacity,
+            batches: Vec::new(),
+
+            object_bind_group_layout,
+            object_buffer,
+            object_bind_group,
+            object_capacity,
+
+            staging: StagingBelt::new(staging::DEFAULT_CHUNK_SIZE),
 
             uniforms,
             uniform_buffer,
             uniform_bind_group,
 
             depth_tex,
-            opaque_bind_group,
+            texture_bind_group_layout: opaque_bind_group_layout,
             opaque_pipeline,
 
             accum_tex,
@@ -598,9 +715,20 @@ impl Renderer {
             screen_bind_group,
             screen_pipeline,
 
+            overlay_pipeline,
+            overlay: false,
+
+            capture_target,
+            capture_buffer,
+
             camera,
+            controllers,
+            controller: 0,
+            playback: None,
+
+            visible: 0,
+            culled: 0,
             size,
-            indices_len,
         }
     }
 
@@ -608,49 +736,335 @@ impl Renderer {
         &mut self.camera
     }
 
+    // cycles through the camera controllers, each one picks up where it was left
+    pub fn next_controller(&mut self) -> &str{
+        self.playback = None;
+        self.controller = (self.controller + 1) % self.controllers.len();
+        let controller = &mut self.controllers[self.controller];
+        controller.activate(&mut self.camera);
+        controller.name()
+    }
+
+    pub fn mouse_motion(&mut self, dx: f32, dy: f32, input: &InputState){
+        let camera = &mut self.camera;
+        match &mut self.playback{
+            Some(player) => player.mouse_motion(camera, dx, dy, input),
+            None => self.controllers[self.controller].mouse_motion(camera, dx, dy, input),
+        }
+    }
+
+    pub fn zoom(&mut self, steps: f32){
+        let camera = &mut self.camera;
+        let fovy = camera.fovy;
+        match &mut self.playback{
+            Some(player) => player.zoom(camera, steps),
+            None => self.controllers[self.controller].zoom(camera, steps),
+        }
+        // zooming follows the wheel right away, same as mouse look
+        camera.prev_fovy += camera.fovy - fovy;
+    }
+
+    pub fn play_path(&mut self, path: CameraPath){
+        let mut player = PathPlayer::new(path);
+        player.activate(&mut self.camera);
+        self.playback = Some(player);
+    }
+
+    // hands the camera back to the active controller where it left it
+    pub fn stop_path(&mut self){
+        if self.playback.take().is_some(){
+            self.controllers[self.controller].activate(&mut self.camera);
+        }
+    }
+
+    pub fn is_playing_path(&self) -> bool{
+        self.playback.is_some()
+    }
+
+    // (visible, culled) entity counts of the last render
+    pub fn get_cull_stats(&self) -> (usize, usize){
+        (self.visible, self.culled)
+    }
+
+    pub fn get_staging(&self) -> &StagingBelt{
+        &self.staging
+    }
+
+    fn create_instance_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer{
+        device.create_buffer(&wgpu::BufferDescriptor{
+            label: Some("instance buffer"),
+            size: (capacity * std::mem::size_of::<Instance>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
+        })
+    }
+
+    fn create_object_buffer(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, capacity: usize) -> (wgpu::Buffer, wgpu::BindGroup){
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor{
+            label: Some("object uniform buffer"),
+            size: capacity as wgpu::BufferAddress * OBJECT_UNIFORM_STRIDE,
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        });
+
+        // the range is a single object, the dynamic offset picks which one
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor{
+            label: Some("object bind group"),
+            layout,
+            bindings: &[
+            wgpu::Binding{
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer{
+                    buffer: &buffer,
+                    range: 0..std::mem::size_of::<ObjectUniforms>() as wgpu::BufferAddress,
+                }
+            }
+            ],
+        });
+
+        (buffer, bind_group)
+    }
+
+    // writes one aligned ObjectUniforms per batch into the shared buffer
+    fn prepare_objects(&mut self, encoder: &mut wgpu::CommandEncoder){
+        if self.batches.is_empty(){
+            return;
+        }
+
+        if self.batches.len() > self.object_capacity{
+            self.object_capacity = self.batches.len().next_power_of_two();
+            let (buffer, bind_group) = Self::create_object_buffer(&self.device, &self.object_bind_group_layout, self.object_capacity);
+            self.object_buffer = buffer;
+            self.object_bind_group = bind_group;
+        }
+
+        let stride = OBJECT_UNIFORM_STRIDE as usize;
+        let mut data = vec![0u8; self.batches.len() * stride];
+        for (i, batch) in self.batches.iter_mut().enumerate(){
+            let object = ObjectUniforms{
+                tint: self.materials[batch.material].tint,
+            };
+            let bytes = bytemuck::bytes_of(&object);
+            data[i * stride..i * stride + bytes.len()].copy_from_slice(bytes);
+            batch.offset = (i * stride) as wgpu::DynamicOffset;
+        }
+
+        self.staging.write_buffer(&self.device, encoder, &self.object_buffer, 0, &data);
+    }
+
+    // groups the visible part of the scene by mesh and material and uploads one model matrix per entity
+    fn prepare_instances(&mut self, encoder: &mut wgpu::CommandEncoder){
+        let frustum = Frustum::from_matrix(&(self.uniforms.projection * self.uniforms.view), self.camera.depth);
+        let total = self.scene.len();
+        let meshes = &self.meshes;
+        let mut entities: Vec<_> = self.scene.iter()
+            .map(|(id, e)| ((e.mesh, e.material), self.scene.world_matrix(id).unwrap_or_else(Mat4::identity)))
+            .filter(|((mesh, _), model)| frustum.contains_aabb(&meshes[*mesh].bounds.transform(model)))
+            .collect();
+        entities.sort_by_key(|(key, _)| *key);
+
+        self.visible = entities.len();
+        self.culled = total - entities.len();
+
+        self.batches.clear();
+        let mut instances = Vec::with_capacity(entities.len());
+        for ((mesh, material), model) in entities{
+            let index = instances.len() as u32;
+            match self.batches.last_mut(){
+                Some(batch) if batch.mesh == mesh && batch.material == material => batch.instances.end = index + 1,
+                _ => self.batches.push(Batch{ mesh, material, instances: index..index + 1, offset: 0 }),
+            }
+            instances.push(Instance{ model: model.to_cols_array_2d() });
+        }
+
+        if instances.is_empty(){
+            return;
+        }
+
+        if instances.len() > self.instance_capacity{
+            self.instance_capacity = instances.len().next_power_of_two();
+            self.instance_buffer = Self::create_instance_buffer(&self.device, self.instance_capacity);
+        }
+
+        self.staging.write_buffer(&self.device, encoder, &self.instance_buffer, 0, bytemuck::cast_slice(&instances));
+    }
+
+    // `texture` is a file name inside res/img
+    fn load_material(&self, name: &str, texture: &str) -> Result<Material, anyhow::Error>{
+        let img_path = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/res/img"));
+        let img_bytes = std::fs::read(img_path.join(texture))?;
+        let (img_tex, cmd_buffer) = Texture::from_bytes(&self.device, &img_bytes)?;
+        self.queue.submit(&[cmd_buffer]);
+
+        Ok(Material::new(&self.device, &self.texture_bind_group_layout, name, texture, img_tex))
+    }
+
+    // replaces everything in the scene, meshes and materials with the contents of `file`.
+    // everything is built and checked before the old scene is dropped, so a bad file leaves it as it was
+    pub fn load_scene(&mut self, file: &SceneFile) -> Result<(), anyhow::Error>{
+        let meshes: Vec<Mesh> = file.meshes.iter()
+            .map(|mesh| Mesh::new(&self.device, &mesh.name, &mesh.vertices, &mesh.indices))
+            .collect();
+
+        let mut materials = Vec::with_capacity(file.materials.len());
+        for material in &file.materials{
+            let mut loaded = self.load_material(&material.name, &material.texture)?;
+            loaded.tint = material.tint;
+            materials.push(loaded);
+        }
+
+        let mut entities = Vec::with_capacity(file.entities.len());
+        for (index, entity) in file.entities.iter().enumerate(){
+            let mesh = meshes.iter().position(|m| m.name == entity.mesh)
+                .ok_or_else(|| anyhow::anyhow!("Unknown mesh: {}", entity.mesh))?;
+            let material = materials.iter().position(|m| m.name == entity.material)
+                .ok_or_else(|| anyhow::anyhow!("Unknown material: {}", entity.material))?;
+            if let Some(parent) = entity.parent{
+                if parent >= file.entities.len(){
+                    return Err(anyhow::anyhow!("Unknown parent entity: {}", parent));
+                }
+            }
+
+            // a zero quaternion normalizes to NaN, which would spread to every child's world matrix
+            let rotation = Vec4::from(entity.rotation);
+            if !(rotation.length() > std::f32::EPSILON){
+                return Err(anyhow::anyhow!("Zero length rotation on entity: {}", index));
+            }
+            let rotation = Quat::from(rotation).normalize();
+            entities.push(Entity{
+                transform: Transform::new(entity.translation.into(), rotation, entity.scale.into()),
+                parent: None,
+                mesh,
+                material,
+            });
+        }
+
+        self.meshes = meshes;
+        self.materials = materials;
+        self.batches.clear();
+        self.scene.clear();
+
+        for light in &file.lights{
+            self.scene.lights.push(Light{
+                position: light.position.into(),
+                color: light.color.into(),
+                intensity: light.intensity,
+            });
+        }
+
+        let ids: Vec<_> = entities.into_iter().map(|entity| self.scene.add(entity)).collect();
+
+        // parents can come after their children in the file, so they are linked once all exist
+        for (entity, id) in file.entities.iter().zip(&ids){
+            if let Some(parent) = entity.parent{
+                self.scene.get_mut(*id).unwrap().parent = Some(ids[parent]);
+            }
+        }
+        self.scene.update_world_matrices();
+
+        let camera = &mut self.camera;
+        camera.eye = file.camera.eye.into();
+        camera.fovy = file.camera.fovy.to_radians();
+        camera.look(file.camera.yaw, file.camera.pitch, file.camera.roll);
+        camera.save_previous();
+
+        self.playback = None;
+        for controller in &mut self.controllers{
+            controller.reset(&self.camera);
+        }
+
+        Ok(())
+    }
+
+    pub fn save_scene(&self) -> SceneFile{
+        let camera = CameraDesc{
+            eye: self.camera.eye.into(),
+            yaw: self.camera.yaw,
+            pitch: self.camera.pitch,
+            roll: self.camera.roll,
+            fovy: self.camera.fovy.to_degrees(),
+        };
+
+        let meshes = self.meshes.iter().map(|m| MeshDesc{
+            name: m.name.clone(),
+            vertices: m.vertices.clone(),
+            indices: m.indices.clone(),
+        }).collect();
+
+        let materials = self.materials.iter().map(|m| MaterialDesc{
+            name: m.name.clone(),
+            texture: m.source.clone(),
+            tint: m.tint,
+        }).collect();
+
+        let lights = self.scene.lights.iter().map(|l| LightDesc{
+            position: l.position.into(),
+            color: l.color.into(),
+            intensity: l.intensity,
+        }).collect();
+
+        let ids: Vec<_> = self.scene.iter().map(|(id, _)| id).collect();
+        let entities = self.scene.iter().map(|(_, e)| EntityDesc{
+            mesh: self.meshes[e.mesh].name.clone(),
+            material: self.materials[e.material].name.clone(),
+            translation: e.transform.translation.into(),
+            rotation: Vec4::from(e.transform.rotation).into(),
+            scale: e.transform.scale.into(),
+            parent: e.parent.and_then(|p| ids.iter().position(|id| *id == p)),
+        }).collect();
+
+        SceneFile{
+            camera,
+            meshes,
+            materials,
+            lights,
+            entities,
+        }
+    }
+
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
         self.size = new_size;
         self.sc_desc.width = new_size.width;
         self.sc_desc.height = new_size.height;
+        self.camera.set_viewport(new_size.width, new_size.height);
         self.depth_tex = Texture::create_depth(&self.device, &self.sc_desc, "depth texture");
         self.accum_tex = Texture::create_empty(&self.device, &self.sc_desc, wgpu::TextureFormat::Rgba16Float, "accum tex");
         self.revealage_tex = Texture::create_empty(&self.device, &self.sc_desc, wgpu::TextureFormat::R8Unorm, "revealage tex");
-        self.swap_chain = self.device.create_swap_chain(&self.surface, &self.sc_desc);
+        match &mut self.target{
+            Target::Window{ surface, swap_chain } => {
+                *swap_chain = self.device.create_swap_chain(surface, &self.sc_desc);
+            },
+            Target::Offscreen(texture) => {
+                *texture = Texture::create_target(&self.device, &self.sc_desc, "offscreen target");
+            },
+        }
+        if self.capture_target.is_some(){
+            self.capture_target = Some(Texture::create_target(&self.device, &self.sc_desc, "capture target"));
+        }
+        self.capture_buffer = capture::create_buffer(&self.device, new_size.width, new_size.height);
     }
 
-    pub fn update(&mut self, dt: f32){
-        self.camera.update(dt);
+    pub fn update(&mut self, dt: f32, input: &InputState){
+        let finished = match &mut self.playback{
+            Some(player) => {
+                player.update(&mut self.camera, dt, input);
+                player.is_finished()
+            },
+            None => {
+                self.controllers[self.controller].update(&mut self.camera, dt, input);
+                false
+            },
+        };
+        if finished{
+            println!("Camera path finished");
+            self.stop_path();
+        }
+        // uploaded once per frame by `render`, which interpolates it first
         self.uniforms.update_view(&self.camera);
-
-        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: Some("update encoder"),
-        });
-
-        let staging_buffer = self.device.create_buffer_with_data(
-            bytemuck::cast_slice(&[self.uniforms]),
-            wgpu::BufferUsage::COPY_SRC,
-        );
-
-        encoder.copy_buffer_to_buffer(&staging_buffer, 0, &self.uniform_buffer, 0, std::mem::size_of::<Uniforms>() as wgpu::BufferAddress);
-
-        self.queue.submit(&[encoder.finish()]);
     }
 
-    fn clear(&mut self, encoder: &mut wgpu::CommandEncoder, output_view: &wgpu::TextureView){
+    // the output is cleared by `composite`
+    fn clear(&self, encoder: &mut wgpu::CommandEncoder){
         encoder.begin_render_pass(&wgpu::RenderPassDescriptor{
             color_attachments: &[
-                wgpu::RenderPassColorAttachmentDescriptor{
-                    attachment: output_view,
-                    resolve_target: None,
-                    load_op: wgpu::LoadOp::Clear,
-                    store_op: wgpu::StoreOp::Store,
-                    clear_color: wgpu::Color{
-                        r: 0.1,
-                        g: 0.2,
-                        b: 0.3,
-                        a: 1.0,
-                    },
-                },
                 wgpu::RenderPassColorAttachmentDescriptor{
                     attachment: &self.accum_tex.view,
                     resolve_target: None,
@@ -680,7 +1094,7 @@ impl Renderer {
                 attachment: &self.depth_tex.view,
                 depth_load_op: wgpu::LoadOp::Clear,
                 depth_store_op: wgpu::StoreOp::Store,
-                clear_depth: 1.0,
+                clear_depth: self.camera.depth.clear_depth(),
                 stencil_load_op: wgpu::LoadOp::Clear,
                 stencil_store_op: wgpu::StoreOp::Store,
                 clear_stencil: 0,
@@ -688,87 +1102,108 @@ impl Renderer {
         });
     }
 
-    pub fn render(&mut self) {
-        let frame = self.swap_chain.get_next_texture().expect("Couldn't get texture");
+    pub fn render(&mut self, alpha: f32) {
+        self.uniforms.update_interpolated_view(&self.camera, alpha);
+        self.scene.update_world_matrices();
+
         let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor{
             label: Some("Renderer encoder"),
         });
+        self.staging.write_buffer(&self.device, &mut encoder, &self.uniform_buffer, 0, bytemuck::cast_slice(&[self.uniforms]));
+        self.prepare_instances(&mut encoder);
+        self.prepare_objects(&mut encoder);
 
-        let models = [
-            Vec3::new(-2., 0., -2.),
-            Vec3::new(0., 0., 0.),
-            Vec3::new(2., 0., 2.),
-        ];
+        let frame = match &mut self.target{
+            Target::Window{ swap_chain, .. } => Some(swap_chain.get_next_texture().expect("Couldn't get texture")),
+            Target::Offscreen(_) => None,
+        };
+        let output_view = match (&frame, &self.target){
+            (Some(frame), _) => &frame.view,
+            (None, Target::Offscreen(texture)) => &texture.view,
+            (None, Target::Window{ .. }) => unreachable!(),
+        };
+
+        self.draw(&mut encoder, output_view);
 
-        self.clear(&mut encoder, &frame.view);
-
-        for model in models.iter().rev(){
-            self.uniforms.update_model(*model);
-            let staging_buffer = self.device.create_buffer_with_data(
-                bytemuck::cast_slice(&[self.uniforms]),
-                wgpu::BufferUsage::COPY_SRC,
-            );
-            encoder.copy_buffer_to_buffer(&staging_buffer, 0, &self.uniform_buffer, 0, std::mem::size_of::<Uniforms>() as wgpu::BufferAddress);
-
-            {
-                let mut transparency_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor{
-                    color_attachments: &[
-                        wgpu::RenderPassColorAttachmentDescriptor{
-                            attachment: &self.accum_tex.view,
-                            resolve_target: None,
-                            load_op: wgpu::LoadOp::Load,
-                            store_op: wgpu::StoreOp::Store,
-                            clear_color: wgpu::Color{
-                                r: 0.,
-                                g: 0.,
-                                b: 0.,
-                                a: 0.,
-                            },
+        self.staging.finish();
+        self.queue.submit(&[
+            encoder.finish()
+        ]);
+        self.staging.recall(&self.device);
+        self.staging.end_frame();
+    }
+
+    fn draw(&self, encoder: &mut wgpu::CommandEncoder, output_view: &wgpu::TextureView){
+        self.clear(encoder);
+
+        {
+            let mut transparency_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor{
+                color_attachments: &[
+                    wgpu::RenderPassColorAttachmentDescriptor{
+                        attachment: &self.accum_tex.view,
+                        resolve_target: None,
+                        load_op: wgpu::LoadOp::Load,
+                        store_op: wgpu::StoreOp::Store,
+                        clear_color: wgpu::Color{
+                            r: 0.,
+                            g: 0.,
+                            b: 0.,
+                            a: 0.,
                         },
-                        wgpu::RenderPassColorAttachmentDescriptor{
-                            attachment: &self.revealage_tex.view,
-                            resolve_target: None,
-                            load_op: wgpu::LoadOp::Load,
-                            store_op: wgpu::StoreOp::Store,
-                            clear_color: wgpu::Color{
-                                r: 1.,
-                                g: 0.,
-                                b: 0.,
-                                a: 0.,
-                            },
-                        }
-                    ],
-                    depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachmentDescriptor{
-                        attachment: &self.depth_tex.view,
-                        depth_load_op: wgpu::LoadOp::Load,
-                        depth_store_op: wgpu::StoreOp::Store,
-                        clear_depth: 0.,
-                        stencil_load_op: wgpu::LoadOp::Load,
-                        stencil_store_op: wgpu::StoreOp::Store,
-                        clear_stencil: 0,
-                    }),
-                });
-
-                transparency_pass.set_pipeline(&self.transparency_pipeline);
-
-                transparency_pass.set_bind_group(0, &self.opaque_bind_group, &[]);
-                transparency_pass.set_bind_group(1, &self.uniform_bind_group, &[]);
-
-                transparency_pass.set_vertex_buffer(0, &self.vertex_buffer, 0, 0);
-                transparency_pass.set_index_buffer(&self.index_buffer, 0, 0);
-
-                transparency_pass.draw_indexed(0..self.indices_len, 0, 0..1);
-            }
+                    },
+                    wgpu::RenderPassColorAttachmentDescriptor{
+                        attachment: &self.revealage_tex.view,
+                        resolve_target: None,
+                        load_op: wgpu::LoadOp::Load,
+                        store_op: wgpu::StoreOp::Store,
+                        clear_color: wgpu::Color{
+                            r: 1.,
+                            g: 0.,
+                            b: 0.,
+                            a: 0.,
+                        },
+                    }
+                ],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachmentDescriptor{
+                    attachment: &self.depth_tex.view,
+                    depth_load_op: wgpu::LoadOp::Load,
+                    depth_store_op: wgpu::StoreOp::Store,
+                    clear_depth: 0.,
+                    stencil_load_op: wgpu::LoadOp::Load,
+                    stencil_store_op: wgpu::StoreOp::Store,
+                    clear_stencil: 0,
+                }),
+            });
+
+            transparency_pass.set_pipeline(&self.transparency_pipeline);
+            transparency_pass.set_bind_group(1, &self.uniform_bind_group, &[]);
+            transparency_pass.set_vertex_buffer(1, &self.instance_buffer, 0, 0);
 
+            for batch in &self.batches{
+                let mesh = &self.meshes[batch.mesh];
+                let material = &self.materials[batch.material];
 
+                transparency_pass.set_bind_group(0, &material.bind_group, &[]);
+                transparency_pass.set_bind_group(2, &self.object_bind_group, &[batch.offset]);
+                transparency_pass.set_vertex_buffer(0, &mesh.vertex_buffer, 0, 0);
+                transparency_pass.set_index_buffer(&mesh.index_buffer, 0, 0);
+
+                transparency_pass.draw_indexed(0..mesh.indices_len, 0, batch.instances.clone());
+            }
         }
+
+        self.composite(encoder, output_view);
+    }
+
+    // resolves the accumulation textures `draw` left behind onto `output_view`
+    fn composite(&self, encoder: &mut wgpu::CommandEncoder, output_view: &wgpu::TextureView){
         {
             let mut screen_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor{
                 color_attachments: &[
                 wgpu::RenderPassColorAttachmentDescriptor{
-                    attachment: &frame.view,
+                    attachment: output_view,
                     resolve_target: None,
-                    load_op: wgpu::LoadOp::Load,
+                    load_op: wgpu::LoadOp::Clear,
                     store_op: wgpu::StoreOp::Store,
                     clear_color: wgpu::Color{
                         r: 0.1,
@@ -787,8 +1222,64 @@ impl Renderer {
             screen_pass.draw(0..6, 0..1);
         }
 
-        self.queue.submit(&[
-            encoder.finish()
-        ]);
+        if self.overlay{
+            let mut overlay_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor{
+                color_attachments: &[
+                wgpu::RenderPassColorAttachmentDescriptor{
+                    attachment: output_view,
+                    resolve_target: None,
+                    load_op: wgpu::LoadOp::Load,
+                    store_op: wgpu::StoreOp::Store,
+                    clear_color: wgpu::Color{
+                        r: 0.,
+                        g: 0.,
+                        b: 0.,
+                        a: 1.,
+                    },
+                }
+                ],
+                depth_stencil_attachment: None,
+            });
+
+            overlay_pass.set_pipeline(&self.overlay_pipeline);
+            overlay_pass.draw(0..6, 0..1);
+        }
+    }
+
+    // reads back the last rendered frame, a window target composites it again from the accumulation
+    // textures into `capture_target`, which skips drawing the scene a second time
+    pub async fn capture(&self) -> Result<image::RgbaImage, anyhow::Error>{
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor{
+            label: Some("capture encoder"),
+        });
+
+        let target = match (&self.target, &self.capture_target){
+            (Target::Offscreen(texture), _) => texture,
+            (Target::Window{ .. }, Some(texture)) => {
+                self.composite(&mut encoder, &texture.view);
+                texture
+            },
+            (Target::Window{ .. }, None) => unreachable!(),
+        };
+
+        let (width, height) = (self.sc_desc.width, self.sc_desc.height);
+        let bytes_per_row = capture::copy_to_buffer(&mut encoder, &target.texture, &self.capture_buffer, width, height);
+        self.queue.submit(&[encoder.finish()]);
+
+        // dropping the mapping at the end unmaps the buffer for the next capture
+        let mapping = self.capture_buffer.map_read(0, (bytes_per_row * height) as wgpu::BufferAddress);
+        self.device.poll(wgpu::Maintain::Wait);
+        let mapping = mapping.await.map_err(|_| anyhow::anyhow!("Couldn't map capture buffer"))?;
+
+        capture::to_rgba(mapping.as_slice(), self.sc_desc.format, width, height, bytes_per_row)
+    }
+
+    pub fn screenshot(&self, path: &Path) -> Result<(), anyhow::Error>{
+        let img = futures::executor::block_on(self.capture())?;
+        if let Some(parent) = path.parent(){
+            std::fs::create_dir_all(parent)?;
+        }
+        img.save(path)?;
+        Ok(())
     }
 }
@@ -0,0 +1,87 @@
+use std::path::Path;
+use serde::{Serialize, Deserialize};
+use crate::input::Binding;
+
+// the part of the winit event stream that changes the simulation
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum InputEvent{
+    Press(Binding),
+    Release(Binding),
+    Wheel(Binding),
+    MouseMotion(f64, f64),
+    FocusLost,
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct Recorded{
+    // the tick that will run right after the event
+    pub tick: u64,
+    pub event: InputEvent,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Recording{
+    pub ups: u32,
+    pub events: Vec<Recorded>,
+}
+
+impl Recording{
+    pub fn new(ups: u32) -> Self{
+        Self{
+            ups,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, tick: u64, event: InputEvent){
+        self.events.push(Recorded{ tick, event });
+    }
+
+    pub fn load(path: &Path) -> Result<Self, anyhow::Error>{
+        let src = std::fs::read_to_string(path)?;
+        let recording: Self = ron::de::from_str(&src)?;
+        // the Timer is set to this rate before replaying
+        if recording.ups == 0{
+            anyhow::bail!("Recording needs at least one update per second");
+        }
+        Ok(recording)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), anyhow::Error>{
+        let src = ron::ser::to_string(self)?;
+        std::fs::write(path, src)?;
+        Ok(())
+    }
+}
+
+pub struct Replay{
+    recording: Recording,
+    cursor: usize,
+}
+
+impl Replay{
+    pub fn new(recording: Recording) -> Self{
+        Self{
+            recording,
+            cursor: 0,
+        }
+    }
+
+    pub fn get_ups(&self) -> u32{
+        self.recording.ups
+    }
+
+    // every event stamped up to `tick` that wasn't returned yet
+    pub fn next_events(&mut self, tick: u64) -> &[Recorded]{
+        let start = self.cursor;
+        let events = &self.recording.events;
+        while self.cursor < events.len() && events[self.cursor].tick <= tick{
+            self.cursor += 1;
+        }
+        &events[start..self.cursor]
+    }
+
+    pub fn is_finished(&self) -> bool{
+        self.cursor >= self.recording.events.len()
+    }
+}
@@ -0,0 +1,261 @@
+use glam::{Vec3, Quat, Mat4};
+
+// indices into the Renderer's mesh and material lists
+pub type MeshId = usize;
+pub type MaterialId = usize;
+
+// local to the parent entity, or to the world for root entities
+#[derive(Copy, Clone, Debug)]
+pub struct Transform{
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Transform{
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn identity() -> Self{
+        Self{
+            translation: Vec3::zero(),
+            rotation: Quat::identity(),
+            scale: Vec3::one(),
+        }
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn from_translation(translation: Vec3) -> Self{
+        Self{
+            translation,
+            ..Self::identity()
+        }
+    }
+
+    pub fn new(translation: Vec3, rotation: Quat, scale: Vec3) -> Self{
+        Self{
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    pub fn matrix(&self) -> Mat4{
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+}
+
+// not used for shading yet, only carried by the scene
+#[derive(Copy, Clone, Debug)]
+pub struct Light{
+    pub position: Vec3,
+    pub color: Vec3,
+    pub intensity: f32,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Entity{
+    pub transform: Transform,
+    pub parent: Option<EntityId>,
+    pub mesh: MeshId,
+    pub material: MaterialId,
+}
+
+// slot index plus a generation, so ids of removed entities don't alias new ones
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct EntityId{
+    index: usize,
+    generation: u32,
+}
+
+struct Slot{
+    generation: u32,
+    entity: Option<Entity>,
+    world: Mat4,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Visit{
+    Pending,
+    InProgress,
+    Done,
+}
+
+pub struct Scene{
+    slots: Vec<Slot>,
+    free: Vec<usize>,
+    pub lights: Vec<Light>,
+}
+
+impl Scene{
+    pub fn new() -> Self{
+        Self{
+            slots: Vec::new(),
+            free: Vec::new(),
+            lights: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, entity: Entity) -> EntityId{
+        match self.free.pop(){
+            Some(index) => {
+                let slot = &mut self.slots[index];
+                slot.entity = Some(entity);
+                EntityId{ index, generation: slot.generation }
+            },
+            None => {
+                self.slots.push(Slot{ generation: 0, entity: Some(entity), world: Mat4::identity() });
+                EntityId{ index: self.slots.len() - 1, generation: 0 }
+            },
+        }
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn remove(&mut self, id: EntityId) -> Option<Entity>{
+        let slot = self.slots.get_mut(id.index).filter(|s| s.generation == id.generation)?;
+        let entity = slot.entity.take()?;
+        slot.generation += 1;
+        self.free.push(id.index);
+        Some(entity)
+    }
+
+    pub fn get(&self, id: EntityId) -> Option<&Entity>{
+        self.slots.get(id.index)
+            .filter(|s| s.generation == id.generation)
+            .and_then(|s| s.entity.as_ref())
+    }
+
+    pub fn get_mut(&mut self, id: EntityId) -> Option<&mut Entity>{
+        self.slots.get_mut(id.index)
+            .filter(|s| s.generation == id.generation)
+            .and_then(|s| s.entity.as_mut())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (EntityId, &Entity)>{
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            slot.entity.as_ref().map(|e| (EntityId{ index, generation: slot.generation }, e))
+        })
+    }
+
+    // world matrix as of the last `update_world_matrices`
+    pub fn world_matrix(&self, id: EntityId) -> Option<Mat4>{
+        self.slots.get(id.index)
+            .filter(|s| s.generation == id.generation && s.entity.is_some())
+            .map(|s| s.world)
+    }
+
+    // parents are resolved before their children, removed parents and cycles are treated as the world
+    pub fn update_world_matrices(&mut self){
+        let mut visits = vec![Visit::Pending; self.slots.len()];
+        for index in 0..self.slots.len(){
+            self.update_world_matrix(index, &mut visits);
+        }
+    }
+
+    fn update_world_matrix(&mut self, index: usize, visits: &mut Vec<Visit>) -> Mat4{
+        if visits[index] == Visit::Done{
+            return self.slots[index].world;
+        }
+
+        let entity = match self.slots[index].entity{
+            Some(entity) => entity,
+            None => {
+                visits[index] = Visit::Done;
+                return Mat4::identity();
+            },
+        };
+
+        visits[index] = Visit::InProgress;
+        let parent = entity.parent
+            .filter(|p| self.get(*p).is_some() && visits[p.index] != Visit::InProgress)
+            .map(|p| self.update_world_matrix(p.index, visits))
+            .unwrap_or_else(Mat4::identity);
+
+        let world = parent * entity.transform.matrix();
+        self.slots[index].world = world;
+        visits[index] = Visit::Done;
+        world
+    }
+
+    pub fn len(&self) -> usize{
+        self.slots.len() - self.free.len()
+    }
+
+    pub fn clear(&mut self){
+        for (index, slot) in self.slots.iter_mut().enumerate(){
+            if slot.entity.take().is_some(){
+                slot.generation += 1;
+                self.free.push(index);
+            }
+        }
+        self.lights.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn entity(x: f32, y: f32, parent: Option<EntityId>) -> Entity{
+        Entity{
+            transform: Transform::from_translation(Vec3::new(x, y, 0.)),
+            parent,
+            mesh: 0,
+            material: 0,
+        }
+    }
+
+    fn position(scene: &Scene, id: EntityId) -> Vec3{
+        scene.world_matrix(id).unwrap().w_axis().truncate()
+    }
+
+    #[test]
+    fn children_follow_their_parent(){
+        let mut scene = Scene::new();
+        let parent = scene.add(Entity{
+            transform: Transform::new(Vec3::new(1., 0., 0.), Quat::identity(), Vec3::new(2., 2., 2.)),
+            ..entity(0., 0., None)
+        });
+        let child = scene.add(entity(0., 1., Some(parent)));
+        scene.update_world_matrices();
+
+        assert_eq!(position(&scene, parent), Vec3::new(1., 0., 0.));
+        assert_eq!(position(&scene, child), Vec3::new(1., 2., 0.));
+    }
+
+    #[test]
+    fn children_can_come_before_their_parent(){
+        let mut scene = Scene::new();
+        let child = scene.add(entity(0., 1., None));
+        let parent = scene.add(entity(1., 0., None));
+        scene.get_mut(child).unwrap().parent = Some(parent);
+        scene.update_world_matrices();
+
+        assert_eq!(position(&scene, child), Vec3::new(1., 1., 0.));
+    }
+
+    #[test]
+    fn cycles_are_cut_at_the_first_entity_visited(){
+        let mut scene = Scene::new();
+        let a = scene.add(entity(1., 0., None));
+        let b = scene.add(entity(0., 1., Some(a)));
+        scene.get_mut(a).unwrap().parent = Some(b);
+        scene.update_world_matrices();
+
+        // `a` is resolved first, so `b` sees it in progress and hangs off the world
+        assert_eq!(position(&scene, b), Vec3::new(0., 1., 0.));
+        assert_eq!(position(&scene, a), Vec3::new(1., 1., 0.));
+    }
+
+    #[test]
+    fn removed_parents_are_the_world(){
+        let mut scene = Scene::new();
+        let parent = scene.add(entity(1., 0., None));
+        let child = scene.add(entity(0., 1., Some(parent)));
+        scene.remove(parent);
+        // the freed slot is reused, the child's old id for it must not resolve to the new entity
+        scene.add(entity(5., 5., None));
+        scene.update_world_matrices();
+
+        assert_eq!(position(&scene, child), Vec3::new(0., 1., 0.));
+        assert_eq!(scene.world_matrix(parent), None);
+    }
+}
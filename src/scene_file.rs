@@ -0,0 +1,91 @@
+use std::path::Path;
+use serde::{Serialize, Deserialize};
+use crate::mesh::Vertex;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CameraDesc{
+    pub eye: [f32; 3],
+    // degrees, same as Camera::yaw, Camera::pitch and Camera::roll
+    pub yaw: f32,
+    pub pitch: f32,
+    #[serde(default)]
+    pub roll: f32,
+    pub fovy: f32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MeshDesc{
+    pub name: String,
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u16>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MaterialDesc{
+    pub name: String,
+    // file name inside res/img
+    pub texture: String,
+    #[serde(default = "white")]
+    pub tint: [f32; 4],
+}
+
+fn white() -> [f32; 4]{
+    [1., 1., 1., 1.]
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LightDesc{
+    pub position: [f32; 3],
+    pub color: [f32; 3],
+    pub intensity: f32,
+}
+
+fn identity_rotation() -> [f32; 4]{
+    [0., 0., 0., 1.]
+}
+
+fn unit_scale() -> [f32; 3]{
+    [1., 1., 1.]
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EntityDesc{
+    pub mesh: String,
+    pub material: String,
+    pub translation: [f32; 3],
+    // quaternion as x, y, z, w
+    #[serde(default = "identity_rotation")]
+    pub rotation: [f32; 4],
+    #[serde(default = "unit_scale")]
+    pub scale: [f32; 3],
+    // index of the parent in `SceneFile::entities`, transforms are relative to it
+    #[serde(default)]
+    pub parent: Option<usize>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SceneFile{
+    pub camera: CameraDesc,
+    pub meshes: Vec<MeshDesc>,
+    pub materials: Vec<MaterialDesc>,
+    #[serde(default)]
+    pub lights: Vec<LightDesc>,
+    pub entities: Vec<EntityDesc>,
+}
+
+impl SceneFile{
+    pub fn load(path: &Path) -> Result<Self, anyhow::Error>{
+        let src = std::fs::read_to_string(path)?;
+        let scene = ron::de::from_str(&src)?;
+        Ok(scene)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), anyhow::Error>{
+        let src = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        if let Some(parent) = path.parent(){
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, src)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,140 @@
+use std::future::Future;
+use std::pin::Pin;
+use futures::FutureExt;
+
+type MapFuture = Pin<Box<dyn Future<Output = Result<wgpu::BufferWriteMapping, wgpu::BufferAsyncErr>>>>;
+
+// copy_buffer_to_buffer offsets have to be multiples of this
+const COPY_ALIGNMENT: wgpu::BufferAddress = 4;
+
+pub const DEFAULT_CHUNK_SIZE: wgpu::BufferAddress = 1 << 16;
+
+struct Chunk{
+    buffer: wgpu::Buffer,
+    size: wgpu::BufferAddress,
+    offset: wgpu::BufferAddress,
+}
+
+// staging buffers that live across frames, a chunk is mapped while we write into it,
+// unmapped while the gpu copies out of it and mapped again once that copy is done
+pub struct StagingBelt{
+    chunk_size: wgpu::BufferAddress,
+    active: Vec<(Chunk, wgpu::BufferWriteMapping)>,
+    closed: Vec<Chunk>,
+    recalling: Vec<(Chunk, MapFuture)>,
+    free: Vec<(Chunk, wgpu::BufferWriteMapping)>,
+    chunks: usize,
+
+    frame_bytes: u64,
+    last_frame_bytes: u64,
+}
+
+impl StagingBelt{
+    pub fn new(chunk_size: wgpu::BufferAddress) -> Self{
+        Self{
+            chunk_size,
+            active: Vec::new(),
+            closed: Vec::new(),
+            recalling: Vec::new(),
+            free: Vec::new(),
+            chunks: 0,
+
+            frame_bytes: 0,
+            last_frame_bytes: 0,
+        }
+    }
+
+    fn create_chunk(&mut self, device: &wgpu::Device, size: wgpu::BufferAddress) -> (Chunk, wgpu::BufferWriteMapping){
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor{
+            label: Some("staging chunk"),
+            size,
+            usage: wgpu::BufferUsage::MAP_WRITE | wgpu::BufferUsage::COPY_SRC,
+        });
+
+        // a new buffer has no gpu work pending so this resolves right away
+        let mapping = buffer.map_write(0, size);
+        device.poll(wgpu::Maintain::Wait);
+        let mapping = futures::executor::block_on(mapping).expect("Couldn't map staging chunk");
+
+        self.chunks += 1;
+        (Chunk{ buffer, size, offset: 0 }, mapping)
+    }
+
+    // records a copy of `data` into `target` at `offset`, the copy happens when `encoder` is submitted
+    pub fn write_buffer(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, target: &wgpu::Buffer, offset: wgpu::BufferAddress, data: &[u8]){
+        let len = data.len() as wgpu::BufferAddress;
+        if len == 0{
+            return;
+        }
+        let size = (len + COPY_ALIGNMENT - 1) / COPY_ALIGNMENT * COPY_ALIGNMENT;
+
+        let index = match self.active.iter().position(|(chunk, _)| chunk.offset + size <= chunk.size){
+            Some(index) => index,
+            None => {
+                let chunk = match self.free.iter().position(|(chunk, _)| size <= chunk.size){
+                    Some(index) => self.free.swap_remove(index),
+                    None => self.create_chunk(device, size.max(self.chunk_size)),
+                };
+                self.active.push(chunk);
+                self.active.len() - 1
+            },
+        };
+
+        let (chunk, mapping) = &mut self.active[index];
+        let start = chunk.offset as usize;
+        mapping.as_slice()[start..start + data.len()].copy_from_slice(data);
+        encoder.copy_buffer_to_buffer(&chunk.buffer, chunk.offset, target, offset, len);
+        chunk.offset += size;
+
+        self.frame_bytes += len;
+    }
+
+    // unmaps every chunk written this frame, call before submitting the encoders that copy from them
+    pub fn finish(&mut self){
+        for (chunk, mapping) in self.active.drain(..){
+            drop(mapping);
+            chunk.buffer.unmap();
+            self.closed.push(chunk);
+        }
+    }
+
+    // call after submitting, maps finished chunks again so later frames can reuse them
+    pub fn recall(&mut self, device: &wgpu::Device){
+        for chunk in self.closed.drain(..){
+            let mapping = chunk.buffer.map_write(0, chunk.size);
+            self.recalling.push((chunk, Box::pin(mapping)));
+        }
+
+        device.poll(wgpu::Maintain::Poll);
+
+        let mut pending = Vec::with_capacity(self.recalling.len());
+        for (mut chunk, mut mapping) in self.recalling.drain(..){
+            match (&mut mapping).now_or_never(){
+                Some(Ok(mapping)) => {
+                    chunk.offset = 0;
+                    self.free.push((chunk, mapping));
+                },
+                Some(Err(_)) => {
+                    println!("Couldn't map staging chunk again, dropping it");
+                    self.chunks -= 1;
+                },
+                None => pending.push((chunk, mapping)),
+            }
+        }
+        self.recalling = pending;
+    }
+
+    pub fn end_frame(&mut self){
+        self.last_frame_bytes = self.frame_bytes;
+        self.frame_bytes = 0;
+    }
+
+    // bytes copied through the belt during the last finished frame
+    pub fn get_frame_bytes(&self) -> u64{
+        self.last_frame_bytes
+    }
+
+    pub fn get_chunk_count(&self) -> usize{
+        self.chunks
+    }
+}
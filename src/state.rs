@@ -0,0 +1,56 @@
+use winit::window::Window;
+use crate::renderer::Renderer;
+use crate::timer::Timer;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GameState{
+    Playing,
+    Paused,
+    Menu,
+}
+
+fn grab_cursor(window: &Window, grab: bool){
+    if let Err(e) = window.set_cursor_grab(grab){
+        println!("Couldn't {} mouse, error: {}", if grab { "grab" } else { "release" }, e);
+    }
+
+    window.set_cursor_visible(!grab);
+}
+
+impl GameState{
+    pub fn enter(self, window: &Window, timer: &mut Timer, renderer: &mut Renderer){
+        match self{
+            GameState::Playing => grab_cursor(window, true),
+            GameState::Paused => {
+                timer.pause();
+                renderer.overlay = true;
+            },
+            GameState::Menu => {
+                timer.pause();
+                renderer.overlay = true;
+                println!("Menu: press Escape to resume or Q to quit");
+            },
+        }
+    }
+
+    pub fn exit(self, window: &Window, timer: &mut Timer, renderer: &mut Renderer){
+        match self{
+            GameState::Playing => grab_cursor(window, false),
+            GameState::Paused | GameState::Menu => {
+                timer.resume();
+                renderer.overlay = false;
+            },
+        }
+    }
+
+    // runs the exit hook of the current state and the enter hook of `next`
+    pub fn transition(&mut self, next: GameState, window: &Window, timer: &mut Timer, renderer: &mut Renderer){
+        if *self == next{
+            return;
+        }
+
+        self.exit(window, timer, renderer);
+        *self = next;
+        self.enter(window, timer, renderer);
+    }
+}
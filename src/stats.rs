@@ -0,0 +1,163 @@
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+#[derive(Copy, Clone, Debug)]
+pub struct Summary{
+    pub count: usize,
+    pub avg: Duration,
+    pub min: Duration,
+    pub max: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+}
+
+impl std::fmt::Display for Summary{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result{
+        write!(f, "avg: {:.2}ms min: {:.2}ms max: {:.2}ms p95: {:.2}ms p99: {:.2}ms",
+            ms(self.avg), ms(self.min), ms(self.max), ms(self.p95), ms(self.p99))
+    }
+}
+
+fn ms(duration: Duration) -> f32{
+    duration.as_secs_f32() * 1000.
+}
+
+pub struct Samples{
+    window: VecDeque<Duration>,
+    capacity: usize,
+}
+
+impl Samples{
+    pub fn new(capacity: usize) -> Self{
+        Self{
+            window: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, sample: Duration){
+        if self.window.len() == self.capacity{
+            self.window.pop_front();
+        }
+        self.window.push_back(sample);
+    }
+
+    pub fn summary(&self) -> Option<Summary>{
+        if self.window.is_empty(){
+            return None;
+        }
+
+        let mut sorted: Vec<Duration> = self.window.iter().copied().collect();
+        sorted.sort();
+
+        let count = sorted.len();
+        let total: Duration = sorted.iter().sum();
+        let percentile = |p: f32| sorted[((count - 1) as f32 * p).round() as usize];
+
+        Some(Summary{
+            count,
+            avg: total / count as u32,
+            min: sorted[0],
+            max: sorted[count - 1],
+            p95: percentile(0.95),
+            p99: percentile(0.99),
+        })
+    }
+}
+
+struct TraceRow{
+    frame: u64,
+    time: Duration,
+    frame_time: Duration,
+    update_time: Duration,
+    updates: u32,
+}
+
+pub struct FrameStats{
+    pub frames: Samples,
+    pub updates: Samples,
+
+    start: Instant,
+    last_frame: Option<Instant>,
+    frame_count: u64,
+
+    frame_updates: u32,
+    frame_update_time: Duration,
+
+    trace: Option<Vec<TraceRow>>,
+}
+
+impl FrameStats{
+    pub const WINDOW: usize = 240;
+
+    pub fn new() -> Self{
+        Self{
+            frames: Samples::new(Self::WINDOW),
+            updates: Samples::new(Self::WINDOW),
+
+            start: Instant::now(),
+            last_frame: None,
+            frame_count: 0,
+
+            frame_updates: 0,
+            frame_update_time: Duration::new(0, 0),
+
+            trace: None,
+        }
+    }
+
+    // keeps every frame in memory so it can be written with `write_csv`
+    pub fn with_trace() -> Self{
+        let mut stats = Self::new();
+        stats.trace = Some(Vec::new());
+        stats
+    }
+
+    pub fn record_update(&mut self, time: Duration){
+        self.updates.push(time);
+        self.frame_updates += 1;
+        self.frame_update_time += time;
+    }
+
+    pub fn record_frame(&mut self){
+        let now = Instant::now();
+        let frame_time = match self.last_frame{
+            Some(last) => now - last,
+            None => now - self.start,
+        };
+        self.last_frame = Some(now);
+        self.frames.push(frame_time);
+
+        if let Some(trace) = &mut self.trace{
+            trace.push(TraceRow{
+                frame: self.frame_count,
+                time: now - self.start,
+                frame_time,
+                update_time: self.frame_update_time,
+                updates: self.frame_updates,
+            });
+        }
+
+        self.frame_count += 1;
+        self.frame_updates = 0;
+        self.frame_update_time = Duration::new(0, 0);
+    }
+
+    pub fn write_csv(&self, path: &Path) -> Result<(), anyhow::Error>{
+        let trace = match &self.trace{
+            Some(trace) => trace,
+            None => anyhow::bail!("Frame stats were created without a trace"),
+        };
+
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        writeln!(file, "frame,time_ms,frame_ms,update_ms,updates")?;
+        for row in trace{
+            writeln!(file, "{},{:.3},{:.3},{:.3},{}",
+                row.frame, ms(row.time), ms(row.frame_time), ms(row.update_time), row.updates)?;
+        }
+
+        Ok(())
+    }
+}
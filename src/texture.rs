@@ -149,4 +149,41 @@ impl Texture{
 
         Self { texture, view, sampler }
     }
+
+    // color attachment that can be copied out, used instead of a swap chain frame
+    pub fn create_target(device: &wgpu::Device, sc_desc: &wgpu::SwapChainDescriptor, label: &str) -> Self{
+        let size = wgpu::Extent3d{
+            width: sc_desc.width,
+            height: sc_desc.height,
+            depth: 1,
+        };
+
+        let desc = wgpu::TextureDescriptor{
+            label: Some(label),
+            size,
+            array_layer_count: 1,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: sc_desc.format,
+            usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT
+                |  wgpu::TextureUsage::COPY_SRC,
+        };
+
+        let texture = device.create_texture(&desc);
+        let view = texture.create_default_view();
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor{
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            lod_min_clamp: -100.,
+            lod_max_clamp: 100.,
+            compare: wgpu::CompareFunction::Always,
+        });
+
+        Self { texture, view, sampler }
+    }
 }
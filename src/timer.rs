@@ -1,41 +1,394 @@
 use std::time::{Duration, Instant};
 
-pub struct Timer{
+pub trait Clock{
+    // time elapsed since the clock was created
+    fn now(&self) -> Duration;
+}
+
+pub struct RealClock{
+    start: Instant,
+}
+
+impl RealClock{
+    pub fn new() -> Self{
+        Self{
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Clock for RealClock{
+    fn now(&self) -> Duration{
+        self.start.elapsed()
+    }
+}
+
+// only moves when told to, used to drive the Timer deterministically
+#[cfg_attr(not(test), allow(dead_code))]
+pub struct VirtualClock{
+    now: Duration,
+}
+
+#[cfg_attr(not(test), allow(dead_code))]
+impl VirtualClock{
+    pub fn new() -> Self{
+        Self{
+            now: Duration::new(0, 0),
+        }
+    }
+
+    pub fn advance(&mut self, delta: Duration){
+        self.now += delta;
+    }
+
+    pub fn set(&mut self, now: Duration){
+        self.now = now;
+    }
+}
+
+impl Clock for VirtualClock{
+    fn now(&self) -> Duration{
+        self.now
+    }
+}
+
+pub struct Timer<C: Clock = RealClock>{
+    clock: C,
     accumulator: Duration,
     delta: Duration,
-    current: Instant,
+    current: Duration,
+    step: Duration,
+    tick: u64,
+    ticks: u32,
+    max_ticks: u32,
+    max_delta: Duration,
+    dropped: Duration,
+    scale: f32,
+    paused: bool,
+    pending_steps: u32,
 }
 
-impl Timer{
+pub const DEFAULT_UPS: u32 = 20;
+pub const DEFAULT_MAX_TICKS: u32 = 5;
+pub const DEFAULT_MAX_DELTA: Duration = Duration::from_millis(250);
+pub const MIN_SCALE: f32 = 1. / 64.;
+pub const MAX_SCALE: f32 = 64.;
+
+impl Timer<RealClock>{
     pub fn new() -> Self{
+        Self::with_ups(DEFAULT_UPS)
+    }
+
+    pub fn with_ups(ups: u32) -> Self{
+        Self::with_clock(RealClock::new(), ups)
+    }
+}
+
+impl<C: Clock> Timer<C>{
+    pub fn with_clock(clock: C, ups: u32) -> Self{
         let accumulator = Duration::new(0, 0);
         let delta = Duration::new(0, 0);
-        let current = Instant::now();
+        let current = clock.now();
+        let step = Self::step_from_ups(ups);
 
         Self{
+            clock,
             accumulator,
             delta,
             current,
+            step,
+            tick: 0,
+            ticks: 0,
+            max_ticks: DEFAULT_MAX_TICKS,
+            max_delta: DEFAULT_MAX_DELTA,
+            dropped: Duration::new(0, 0),
+            scale: 1.,
+            paused: false,
+            pending_steps: 0,
         }
     }
 
+    fn step_from_ups(ups: u32) -> Duration{
+        assert!(ups > 0, "Timer needs at least one update per second");
+        Duration::from_secs(1) / ups
+    }
+
     pub fn reset(&mut self){
-        let now = Instant::now();
-        self.delta = now - self.current;
+        let now = self.clock.now();
+        // a clock set backwards counts as no time passing
+        let delta = now.checked_sub(self.current).unwrap_or_default();
+        self.advance(now, delta);
+    }
+
+    // advances by `delta` instead of the clock, e.g. to simulate a fixed frame rate while capturing
+    pub fn reset_with_delta(&mut self, delta: Duration){
+        let now = self.clock.now();
+        self.advance(now, delta);
+    }
+
+    // `now` is the clock reading the frame started at, read once so no time falls between two reads
+    fn advance(&mut self, now: Duration, delta: Duration){
         self.current = now;
-        self.accumulator += self.delta;
+        self.delta = delta;
+
+        // whole steps left over from a frame that hit `max_ticks` are never simulated
+        if self.accumulator >= self.step{
+            let leftover = Duration::from_nanos((self.accumulator.as_nanos() % self.step.as_nanos()) as u64);
+            self.dropped += self.accumulator - leftover;
+            self.accumulator = leftover;
+        }
+
+        if !self.paused{
+            let delta = self.delta.min(self.max_delta);
+            self.dropped += self.scaled(self.delta - delta);
+            self.accumulator += self.scaled(delta);
+        }
+
+        self.ticks = 0;
+    }
+
+    // in whole nanoseconds, Duration::mul_f32 goes through f32 seconds which loses nanoseconds and panics on overflow
+    fn scaled(&self, delta: Duration) -> Duration{
+        Duration::from_nanos((delta.as_nanos() as f64 * self.scale as f64).round() as u64)
     }
 
-    pub const UPS: u64 = 20;
     pub fn should_update(&self) -> bool{
-        self.accumulator >= Duration::from_millis(1000 / Self::UPS)
+        if self.paused{
+            return self.pending_steps > 0;
+        }
+
+        self.ticks < self.max_ticks && self.accumulator >= self.step
     }
 
     pub fn update(&mut self){
-        self.accumulator -= Duration::from_millis(1000 / Self::UPS);
+        if self.paused{
+            self.pending_steps = self.pending_steps.saturating_sub(1);
+        }else{
+            self.accumulator -= self.step;
+        }
+
+        self.ticks += 1;
+        self.tick += 1;
+    }
+
+    // number of fixed ticks simulated so far
+    pub fn get_tick(&self) -> u64{
+        self.tick
+    }
+
+    // speed of the simulation relative to the wall clock, 1.0 is real time, clamped to MIN_SCALE..MAX_SCALE
+    pub fn set_scale(&mut self, scale: f32){
+        self.scale = scale.max(MIN_SCALE).min(MAX_SCALE);
+    }
+
+    pub fn get_scale(&self) -> f32{
+        self.scale
+    }
+
+    pub fn pause(&mut self){
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self){
+        self.paused = false;
+        self.pending_steps = 0;
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn toggle_pause(&mut self){
+        if self.paused{
+            self.resume();
+        }else{
+            self.pause();
+        }
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn is_paused(&self) -> bool{
+        self.paused
+    }
+
+    // queues one fixed tick to run on the next frame, only while paused
+    pub fn single_step(&mut self){
+        if self.paused{
+            self.pending_steps += 1;
+        }
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn set_max_ticks(&mut self, max_ticks: u32){
+        self.max_ticks = max_ticks.max(1);
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn set_max_delta(&mut self, max_delta: Duration){
+        self.max_delta = max_delta;
+    }
+
+    // the longest frame that is simulated without dropping time to `max_delta` or `max_ticks`
+    pub fn get_max_frame(&self) -> Duration{
+        let ticks = Duration::from_nanos((self.step.as_nanos() as f64 * self.max_ticks as f64 / self.scale as f64) as u64);
+        self.max_delta.min(ticks)
+    }
+
+    // total simulation time thrown away by the `max_delta` clamp and the `max_ticks` limit
+    pub fn get_dropped(&self) -> Duration{
+        self.dropped
+    }
+
+    pub fn set_ups(&mut self, ups: u32){
+        self.step = Self::step_from_ups(ups);
+    }
+
+    pub fn get_ups(&self) -> u32{
+        (Duration::from_secs(1).as_nanos() / self.step.as_nanos()) as u32
+    }
+
+    pub fn get_step(&self) -> Duration{
+        self.step
+    }
+
+    // how far we are between the last and the next tick, in [0, 1)
+    pub fn get_alpha(&self) -> f32{
+        (self.accumulator.as_secs_f32() / self.step.as_secs_f32()).min(1.)
     }
 
     pub fn get_delta(&self) -> Duration{
         self.delta
     }
+
+    pub fn now(&self) -> Duration{
+        self.clock.now()
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn get_clock(&self) -> &C{
+        &self.clock
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn get_clock_mut(&mut self) -> &mut C{
+        &mut self.clock
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn timer(ups: u32) -> Timer<VirtualClock>{
+        Timer::with_clock(VirtualClock::new(), ups)
+    }
+
+    // moves the clock by `ms` and runs every tick the timer allows, like one pass of the game loop
+    fn frame(timer: &mut Timer<VirtualClock>, ms: u64) -> u32{
+        timer.get_clock_mut().advance(Duration::from_millis(ms));
+        timer.reset();
+
+        let mut ticks = 0;
+        while timer.should_update(){
+            timer.update();
+            ticks += 1;
+        }
+        ticks
+    }
+
+    #[test]
+    fn ticks_once_per_step(){
+        let mut timer = timer(20);
+        assert_eq!(frame(&mut timer, 50), 1);
+        assert_eq!(timer.get_delta(), Duration::from_millis(50));
+        assert_eq!(frame(&mut timer, 100), 2);
+        assert_eq!(frame(&mut timer, 20), 0);
+        assert_eq!(frame(&mut timer, 30), 1);
+        assert_eq!(timer.get_tick(), 4);
+        assert_eq!(timer.get_clock().now(), Duration::from_millis(200));
+
+        timer.get_clock_mut().set(Duration::from_millis(250));
+        timer.reset();
+        assert!(timer.should_update());
+    }
+
+    #[test]
+    fn clock_set_backwards_is_an_empty_frame(){
+        let mut timer = timer(20);
+        assert_eq!(frame(&mut timer, 75), 1);
+
+        timer.get_clock_mut().set(Duration::from_millis(10));
+        timer.reset();
+        assert_eq!(timer.get_delta(), Duration::new(0, 0));
+        assert!(!timer.should_update());
+    }
+
+    #[test]
+    fn caps_ticks_per_frame(){
+        let mut timer = timer(20);
+        timer.set_max_delta(Duration::from_secs(1));
+        timer.set_max_ticks(5);
+
+        assert_eq!(frame(&mut timer, 400), 5);
+        // the three steps over the cap are dropped instead of running next frame
+        assert_eq!(frame(&mut timer, 0), 0);
+        assert_eq!(timer.get_dropped(), Duration::from_millis(150));
+    }
+
+    #[test]
+    fn clamps_long_frames(){
+        let mut timer = timer(20);
+        timer.set_max_ticks(100);
+
+        assert_eq!(frame(&mut timer, 1000), 5);
+        assert_eq!(timer.get_dropped(), DEFAULT_MAX_DELTA * 3);
+    }
+
+    #[test]
+    fn steps_while_paused(){
+        let mut timer = timer(20);
+        timer.toggle_pause();
+        assert!(timer.is_paused());
+
+        assert_eq!(frame(&mut timer, 500), 0);
+        timer.single_step();
+        assert_eq!(frame(&mut timer, 0), 1);
+        assert_eq!(frame(&mut timer, 0), 0);
+
+        // the half second spent paused is not caught up
+        timer.toggle_pause();
+        assert!(!timer.is_paused());
+        assert_eq!(frame(&mut timer, 50), 1);
+        assert_eq!(timer.get_tick(), 2);
+    }
+
+    #[test]
+    fn scales_and_clamps_speed(){
+        let mut timer = timer(20);
+        timer.set_scale(2.);
+        assert_eq!(frame(&mut timer, 50), 2);
+
+        timer.set_scale(1000.);
+        assert_eq!(timer.get_scale(), MAX_SCALE);
+        timer.set_scale(0.);
+        assert_eq!(timer.get_scale(), MIN_SCALE);
+    }
+
+    #[test]
+    fn max_frame_is_the_tighter_limit(){
+        let mut timer = timer(20);
+        assert_eq!(timer.get_max_frame(), DEFAULT_MAX_DELTA);
+
+        timer.set_max_ticks(2);
+        assert_eq!(timer.get_max_frame(), Duration::from_millis(100));
+        timer.set_scale(2.);
+        assert_eq!(timer.get_max_frame(), Duration::from_millis(50));
+    }
+
+    #[test]
+    fn alpha_is_the_fraction_of_a_step_left(){
+        let mut timer = timer(20);
+        assert_eq!(frame(&mut timer, 25), 0);
+        assert!((timer.get_alpha() - 0.5).abs() < 1e-6);
+
+        assert_eq!(frame(&mut timer, 35), 1);
+        assert!((timer.get_alpha() - 0.2).abs() < 1e-6);
+    }
 }